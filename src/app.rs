@@ -1,20 +1,24 @@
-use std::{sync::Arc, time::Instant, ops::Range, fs::File};
-use cgmath::{Vector4, InnerSpace};
+use std::{sync::Arc, time::Instant, ops::Range};
 use eframe::egui::{self, DragValue};
 use rand::SeedableRng;
 
-use crate::{viewport::Viewport, mesh_operation::{self, MeshOperation, ShowResponse}, vertex::Vertex};
+use crate::{viewport::Viewport, mesh_operation::{self, MeshOperation, ShowResponse}, vertex::{Vertex, Attribute}, layers::{Layers, Chunk}, export};
 
 pub struct App {
 	viewport_size: egui::emath::Vec2,
 	operations: Vec<(f32, MeshOperation)>,
 	mesh: (Vec<Vertex>, Vec<u32>),
+	layers: Layers,
 	should_set: bool,
 	seed: i64,
 	last_seed: i64,
+	tiling: bool,
+	chunks: [u32; 2],
+	chunk_padding: u32,
 	display: u32,
 	material_threshold: f32,
 	material_smoothness: f32,
+	overlay: Option<Attribute>,
 	normals_calculation_time: f32,
 }
 
@@ -54,12 +58,17 @@ impl App {
 				viewport_size,
 				operations,
 				mesh: (Vec::new(), Vec::new()),
+				layers: Layers::default(),
 				should_set: false,
 				seed: -1,
 				last_seed: -1,
+				tiling: false,
+				chunks: [2, 2],
+				chunk_padding: 8,
 				display: 2,
 				material_threshold: 0.7,
 				material_smoothness: 0.1,
+				overlay: None,
 				normals_calculation_time: 0.0,
 			}
 		)
@@ -90,7 +99,7 @@ impl App {
 			let mesh = 
 			if self.should_set {
 				self.should_set = false;
-				Some(self.mesh.clone())
+				Some((self.mesh.clone(), self.layers.markers()))
 			}
 			else {
 				None
@@ -98,17 +107,20 @@ impl App {
 			let display = self.display;
 			let material_threshold =  self.material_threshold;
 			let material_smoothness = self.material_smoothness;
+			let overlay = self.overlay.map_or(-1, |a| a as i32);
 
 			let cb = egui_wgpu::CallbackFn::new()
 			.prepare(move |device, queue, _encoder, paint_callback_resources| {
 				let viewport: &mut Viewport = paint_callback_resources.get_mut().unwrap();
-				if let Some(m) = &mesh {
+				if let Some((m, markers)) = &mesh {
 					viewport.mesh = (m.0.clone(), m.1.clone(), None);
+					viewport.markers = markers.clone();
 					viewport.should_set = true;
 				}
 				viewport.view_uniform.display = display;
 				viewport.view_uniform.material_threshold =  material_threshold;
 				viewport.view_uniform.material_smoothness = material_smoothness;
+				viewport.view_uniform.overlay = overlay;
 				viewport.prepare(device, queue, &response, scroll, resize);
 				Vec::new()
 			})
@@ -127,31 +139,17 @@ impl App {
 	}
 
 	fn generate_mesh(&mut self, range: Range<usize>) -> (Vec<Vertex>, Vec<u32>) {
-		let operations: &mut [(f32, MeshOperation)] = &mut self.operations[range];
-		let mut verts = Vec::<Vertex>::new();
-		let mut inds = Vec::<u32>::new();
-
-		let seed = 
-			if self.seed >= 0 {
-				self.seed as u64
-			} 
-			else {
-				rand::random::<u32>() as u64
-			};
-		self.last_seed = seed as i64;
-
-		let mut random = rand::rngs::StdRng::seed_from_u64(seed);
-
-		let mut start = Instant::now();
-		for op in operations.iter_mut() {
-			(verts, inds) = op.1.execute(verts, inds, &mut random);
-			op.0 = start.elapsed().as_secs_f32();
-			start = Instant::now();
-		}
+		let seed = self.next_seed();
+		let mut layers = Layers { tiling: self.tiling, ..Default::default() };
+		let (mut verts, mut inds) = execute_stack(&mut self.operations[range], seed, &mut layers);
 
 		let normals_start = Instant::now();
-		(verts, inds) = Self::calculate_normals(verts, inds);
+		(verts, inds) = mesh_operation::calculate_normals(verts, inds);
+		if let Some(depth) = layers.skirt {
+			(verts, inds) = mesh_operation::skirt(verts, inds, depth);
+		}
 		self.normals_calculation_time = normals_start.elapsed().as_secs_f32();
+		self.layers = layers;
 
 		if self.display == 0 {
 			
@@ -160,45 +158,74 @@ impl App {
 		(verts, inds)
 	}
 
-	fn calculate_normals(verts: Vec<Vertex>, inds: Vec<u32>) -> (Vec<Vertex>, Vec<u32>) {
-		let mut verts: Vec<Vertex> = verts.iter().map(|v| Vertex {pos: v.pos, normal: [0.0, 0.0, 0.0, 0.0]}).collect();
+	fn next_seed(&mut self) -> u64 {
+		let seed = 
+			if self.seed >= 0 {
+				self.seed as u64
+			} 
+			else {
+				rand::random::<u32>() as u64
+			};
+		self.last_seed = seed as i64;
+		seed
+	}
 
-		for i in 0..(inds.len() / 3) {
-			let i = [
-				inds[3*i+0] as usize,
-				inds[3*i+1] as usize,
-				inds[3*i+2] as usize,
-			];
-			//    1
-			//  /   \
-			// 2-----0
-			const IDS: [[usize; 3]; 3] = [
-				[0, 1, 2],
-				[1, 2, 0],
-				[2, 0, 1],
-			];
+	// generates the chunks one at a time with the same seed and writes each to its own file
+	fn export_chunks(&mut self) {
+		let seed = self.next_seed();
+		for x in 0..self.chunks[0] as i32 {
+			for z in 0..self.chunks[1] as i32 {
+				let chunk = Chunk { x, z, padding: self.chunk_padding, area: None };
+				let (verts, inds) = generate_chunk(&mut self.operations, seed, chunk);
+				export::obj(&format!("{}_chunk_{}_{}.obj", seed, x, z), &verts, &inds).unwrap();
+			}
+		}
+	}
+}
 
-			for [id1, id2, id3] in IDS {
-				let (i1, i2, i3) = (i[id1], i[id2], i[id3]);
+// one chunk of the world, cropped to its area and with the skirt (if any) added
+fn generate_chunk(operations: &mut [(f32, MeshOperation)], seed: u64, chunk: Chunk) -> (Vec<Vertex>, Vec<u32>) {
+	let mut layers = Layers { chunk: Some(chunk), ..Default::default() };
+	let (verts, inds) = execute_stack(operations, seed, &mut layers);
+
+	// the normals are calculated with the padding, so they also match on the borders
+	let (verts, inds) = mesh_operation::calculate_normals(verts, inds);
+	let (verts, inds) = match layers.chunk.and_then(|c| c.area) {
+		Some([min, max]) => mesh_operation::crop(&verts, &inds, min, max),
+		None => (verts, inds),
+	};
+	match layers.skirt {
+		Some(depth) => mesh_operation::skirt(verts, inds, depth),
+		None => (verts, inds),
+	}
+}
 
-				let disp1 = Vector4::from(verts[i2].pos).truncate() - Vector4::from(verts[i1].pos).truncate();
-				let disp2 = Vector4::from(verts[i3].pos).truncate() - Vector4::from(verts[i1].pos).truncate();
-				let normal: [f32; 4] = disp1.cross(disp2).normalize().extend(1.0).into();
-				verts[i1].normal[0] += normal[0];
-				verts[i1].normal[1] += normal[1];
-				verts[i1].normal[2] += normal[2];
-				verts[i1].normal[3] += normal[3];
-			}
+// runs the operations in order, storing the time each one took
+fn execute_stack(operations: &mut [(f32, MeshOperation)], seed: u64, layers: &mut Layers) -> (Vec<Vertex>, Vec<u32>) {
+	let mut verts = Vec::<Vertex>::new();
+	let mut inds = Vec::<u32>::new();
+	let mut random = rand::rngs::StdRng::seed_from_u64(seed);
+
+	let mut start = Instant::now();
+	for (index, op) in operations.iter_mut().enumerate() {
+		// in a chunk every operation gets a random stream of its own, because how many values an operation draws
+		// can depend on the terrain, which differs between chunks, and that mustn't change the operations after it
+		if layers.chunk.is_some() {
+			random = rand::rngs::StdRng::seed_from_u64(seed.wrapping_add((index as u64) << 32));
 		}
 
-		(verts.iter_mut().map(|v| {
-			v.normal[0] /= v.normal[3];
-			v.normal[1] /= v.normal[3];
-			v.normal[2] /= v.normal[3];
+		let tiles = mesh_operation::Tiles::new(&verts, layers.tiling);
+		(verts, inds) = op.1.execute(verts, inds, &mut random, layers);
 
-			*v
-		}).collect(), inds)
+		// the vertices on the opposite edges stay identical after every operation; the twins found before
+		// the operation are only valid if it kept the vertices, otherwise they're found again
+		if layers.tiling {
+			if op.1.keeps_vertices() { tiles } else { mesh_operation::Tiles::new(&verts, true) }.weld(&mut verts);
+		}
+		op.0 = start.elapsed().as_secs_f32();
+		start = Instant::now();
 	}
+	(verts, inds)
 }
 
 impl eframe::App for App {
@@ -219,6 +246,7 @@ impl eframe::App for App {
 					self.seed = -1;
 				}
 			});
+			ui.checkbox(&mut self.tiling, "tileable");
 
 			ui.add_space(20.0);
 			ui.separator();
@@ -278,8 +306,9 @@ impl eframe::App for App {
 
 				egui::ComboBox::from_id_source("combobox").selected_text("Add Operation").show_ui(ui, |ui| {
 
-					for def in MeshOperation::DEFAULTS {
-						ui.selectable_value(&mut selection, Some(def), def.caption());
+					for def in MeshOperation::defaults() {
+						let caption = def.caption().to_string();
+						ui.selectable_value(&mut selection, Some(def), caption);
 					}
 				});
 
@@ -287,6 +316,17 @@ impl eframe::App for App {
 					self.operations.push((0.0, operation));
 				}
 
+				let mut preset: Option<Vec<MeshOperation>> = None;
+				egui::ComboBox::from_id_source("presets").selected_text("Load Preset").show_ui(ui, |ui| {
+					for (name, operations) in MeshOperation::presets() {
+						ui.selectable_value(&mut preset, Some(operations), name);
+					}
+				});
+
+				if let Some(operations) = preset {
+					self.operations = operations.into_iter().map(|o| (0.0, o)).collect();
+				}
+
 				if ui.button("build").clicked() {
 					self.mesh = self.generate_mesh(0..self.operations.len());
 					self.should_set = true;
@@ -294,25 +334,43 @@ impl eframe::App for App {
 
 				
 				if ui.button("export obj").clicked() {
-					use std::io::prelude::*;
-					
-					let mut file = File::create(format!("{}.obj", self.last_seed)).unwrap();
-
-					file.write("# vertices\n".as_bytes()).unwrap();
-					for v in &self.mesh.0 {
-						file.write(format!("v {} {} {}\n", v.pos[0], v.pos[1], v.pos[2]).as_bytes()).unwrap();
-					}
-					file.write("\n# normals\n".as_bytes()).unwrap();
-					for v in &self.mesh.0 {
-						file.write(format!("vn {} {} {}\n", v.normal[0], v.normal[1], v.normal[2]).as_bytes()).unwrap();
-					}
-
-					file.write("\n# faces\n".as_bytes()).unwrap();
-					for i in 0..self.mesh.1.len()/3 {
-						let (i1, i2, i3) = (self.mesh.1[3*i+0]+1, self.mesh.1[3*i+1]+1, self.mesh.1[3*i+2]+1);
-						file.write(format!("f {i1}//{i1} {i2}//{i2} {i3}//{i3}\n").as_bytes()).unwrap();
-					}
+					export::obj(&format!("{}.obj", self.last_seed), &self.mesh.0, &self.mesh.1).unwrap();
+				}
+				if ui.button("export ply").clicked() {
+					export::ply(&format!("{}.ply", self.last_seed), &self.mesh.0, &self.mesh.1).unwrap();
+				}
+				if ui.button("export glb").clicked() {
+					export::glb(&format!("{}.glb", self.last_seed), &self.mesh.0, &self.mesh.1).unwrap();
+				}
+				if !self.layers.instance_sets.is_empty() {
+					ui.horizontal(|ui| {
+						if ui.button("export scatter json").clicked() {
+							export::instances_json(&format!("{}_scatter.json", self.last_seed), &self.layers.instance_sets).unwrap();
+						}
+						if ui.button("csv").clicked() {
+							export::instances_csv(&format!("{}_scatter.csv", self.last_seed), &self.layers.instance_sets).unwrap();
+						}
+					});
+				}
+				if !self.layers.polylines.is_empty() && ui.button("export roads json").clicked() {
+					export::polylines_json(&format!("{}_roads.json", self.last_seed), &self.layers.polylines).unwrap();
+				}
+				if !self.layers.walkable.1.is_empty() && ui.button("export walkable obj").clicked() {
+					export::obj(&format!("{}_walkable.obj", self.last_seed), &self.layers.walkable.0, &self.layers.walkable.1).unwrap();
+				}
+				ui.horizontal(|ui| {
+					ui.label("chunks:");
+					ui.add(DragValue::new(&mut self.chunks[0]).clamp_range(1..=64));
+					ui.add(DragValue::new(&mut self.chunks[1]).clamp_range(1..=64));
+					ui.label("padding:");
+					ui.add(DragValue::new(&mut self.chunk_padding).clamp_range(0..=64));
+				});
+				if ui.button("export chunks").clicked() {
+					self.export_chunks();
+				}
 
+				for report in &self.layers.reports {
+					ui.label(report);
 				}
 			});			
 		});
@@ -341,6 +399,20 @@ impl eframe::App for App {
 					ui.label("Material smoothness:");
 					ui.add(egui::Slider::new(&mut self.material_smoothness, 0.01..=1.0));
 				});
+
+				ui.add_space(40.0);
+
+				ui.vertical(|ui| {
+					ui.label("Overlay:");
+					egui::ComboBox::from_id_source("overlay")
+					.selected_text(self.overlay.map_or("none", |a| a.name()))
+					.show_ui(ui, |ui| {
+						ui.selectable_value(&mut self.overlay, None, "none");
+						for a in Attribute::ALL {
+							ui.selectable_value(&mut self.overlay, Some(a), a.name());
+						}
+					});
+				});
 			});
 			ui.add_space(20.0);
 		});
@@ -358,4 +430,76 @@ impl eframe::App for App {
 			self.viewport_widget(ui);
 		});
 	}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn longest_xz_edge(verts: &[Vertex], inds: &[u32]) -> f32 {
+		inds.chunks_exact(3)
+			.flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+			.map(|(a, b)| {
+				let (a, b) = (verts[a as usize].pos, verts[b as usize].pos);
+				((a[0] - b[0]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+			})
+			.fold(0.0, f32::max)
+	}
+
+	fn largest_height_step(verts: &[Vertex], inds: &[u32]) -> f32 {
+		inds.chunks_exact(3)
+			.flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+			.map(|(a, b)| (verts[a as usize].pos[1] - verts[b as usize].pos[1]).abs())
+			.fold(0.0, f32::max)
+	}
+
+	#[test]
+	fn tiled_displace_smooth_is_continuous() {
+		let step = |tiling: bool| {
+			let mut operations = vec![
+				(0.0, MeshOperation::AddTriSquareGrid(10.0, 400)),
+				(0.0, MeshOperation::DisplaceSmooth(1.0, 1.0, 1, [false, true, false])),
+			];
+			let mut layers = Layers { tiling, ..Default::default() };
+			let (verts, inds) = execute_stack(&mut operations, 1, &mut layers);
+			largest_height_step(&verts, &inds)
+		};
+
+		let (tiled, plain) = (step(true), step(false));
+		assert!(tiled < plain * 2.0, "tiled {tiled}, not tiled {plain}");
+	}
+
+	#[test]
+	fn tiled_remesh_keeps_vertices_in_place() {
+		let mut operations = vec![
+			(0.0, MeshOperation::AddTriSquareGrid(10.0, 20)),
+			(0.0, MeshOperation::Remesh(0.52, 1)),
+		];
+		let mut layers = Layers { tiling: true, ..Default::default() };
+		let (verts, inds) = execute_stack(&mut operations, 1, &mut layers);
+
+		assert!(verts.iter().all(|v| v.pos.iter().all(|p| p.is_finite())));
+		assert!(longest_xz_edge(&verts, &inds) < 1.0);
+	}
+
+	#[test]
+	fn chunk_borders_match() {
+		let mut operations = vec![
+			(0.0, MeshOperation::AddTriSquareGrid(10.0, 20)),
+			(0.0, MeshOperation::DisplaceSmooth(1.0, 1.0, 2, [false, true, false])),
+			(0.0, MeshOperation::Scatter("trees".to_string(), 0.3, 0.3, [-100.0, 100.0], [0.8, 1.2])),
+			(0.0, MeshOperation::DisplaceSmooth(0.5, 0.5, 2, [false, true, false])),
+			(0.0, MeshOperation::Subdivide(1)),
+			(0.0, MeshOperation::Smooth(0.5, 2)),
+		];
+		let (a, _) = generate_chunk(&mut operations, 1, Chunk { x: 0, z: 0, padding: 4, area: None });
+		let (b, _) = generate_chunk(&mut operations, 1, Chunk { x: 1, z: 0, padding: 4, area: None });
+
+		// the vertices at the same place on X and Z are the ones on the border
+		let key = |v: &Vertex| (v.pos[0].to_bits(), v.pos[2].to_bits());
+		let border: std::collections::HashMap<_, _> = b.iter().map(|v| (key(v), *v)).collect();
+		let shared: Vec<(&Vertex, &Vertex)> = a.iter().filter_map(|v| border.get(&key(v)).map(|w| (v, w))).collect();
+
+		assert!(shared.len() > 20);
+		assert!(shared.iter().all(|(v, w)| v == w));
+	}
+}
@@ -0,0 +1,146 @@
+use cgmath::{Vector3, InnerSpace};
+
+use crate::vertex::Vertex;
+
+// Bounding volume hierarchy over the triangles of a mesh, for casting rays against it
+
+const LEAF_SIZE: usize = 4;
+
+struct Node {
+	min: Vector3<f32>,
+	max: Vector3<f32>,
+	// a leaf holds `count` triangles starting at `start`, an inner node has its children at `start` and `start + 1`
+	start: usize,
+	count: usize,
+}
+
+pub struct Bvh {
+	nodes: Vec<Node>,
+	triangles: Vec<[Vector3<f32>; 3]>,
+}
+
+impl Bvh {
+	pub fn new(verts: &[Vertex], inds: &[u32]) -> Self {
+		let pos = |i: u32| Vector3::new(verts[i as usize].pos[0], verts[i as usize].pos[1], verts[i as usize].pos[2]);
+		let triangles: Vec<[Vector3<f32>; 3]> = inds.chunks_exact(3).map(|t| [pos(t[0]), pos(t[1]), pos(t[2])]).collect();
+
+		let mut bvh = Self { nodes: Vec::new(), triangles };
+		if !bvh.triangles.is_empty() {
+			bvh.nodes.push(Node { min: Vector3::new(0.0, 0.0, 0.0), max: Vector3::new(0.0, 0.0, 0.0), start: 0, count: bvh.triangles.len() });
+			bvh.split(0);
+		}
+		bvh
+	}
+
+	// fits the node's bounds to its triangles and splits it in the middle of the longest axis, until the leaves are small enough
+	fn split(&mut self, node: usize) {
+		let (start, count) = (self.nodes[node].start, self.nodes[node].count);
+		let triangles = &mut self.triangles[start..start + count];
+
+		let mut min = Vector3::new(f32::MAX, f32::MAX, f32::MAX);
+		let mut max = Vector3::new(f32::MIN, f32::MIN, f32::MIN);
+		for p in triangles.iter().flatten() {
+			min = Vector3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z));
+			max = Vector3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z));
+		}
+		self.nodes[node].min = min;
+		self.nodes[node].max = max;
+
+		if count <= LEAF_SIZE {
+			return;
+		}
+
+		let size = max - min;
+		let axis = if size.x >= size.y && size.x >= size.z { 0 } else if size.y >= size.z { 1 } else { 2 };
+		let centroid = |t: &[Vector3<f32>; 3]| t[0][axis] + t[1][axis] + t[2][axis];
+		triangles.select_nth_unstable_by(count / 2, |a, b| centroid(a).total_cmp(&centroid(b)));
+
+		let children = self.nodes.len();
+		let empty = Vector3::new(0.0, 0.0, 0.0);
+		self.nodes.push(Node { min: empty, max: empty, start, count: count / 2 });
+		self.nodes.push(Node { min: empty, max: empty, start: start + count / 2, count: count - count / 2 });
+		self.nodes[node].start = children;
+		self.nodes[node].count = 0;
+
+		self.split(children);
+		self.split(children + 1);
+	}
+
+	// whether anything is hit within `max_distance`, `dir` has to be normalized
+	pub fn occluded(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_distance: f32) -> bool {
+		self.traverse(origin, dir, max_distance, true).is_some()
+	}
+
+	fn traverse(&self, origin: Vector3<f32>, dir: Vector3<f32>, max_distance: f32, any: bool) -> Option<f32> {
+		if self.nodes.is_empty() {
+			return None;
+		}
+		let inv = Vector3::new(1.0 / dir.x, 1.0 / dir.y, 1.0 / dir.z);
+
+		let mut closest = max_distance;
+		let mut found = false;
+		let mut stack = vec![0];
+		while let Some(n) = stack.pop() {
+			let node = &self.nodes[n];
+			if !slab_test(node.min, node.max, origin, inv, closest) {
+				continue;
+			}
+
+			if node.count == 0 {
+				stack.push(node.start);
+				stack.push(node.start + 1);
+				continue;
+			}
+
+			for t in &self.triangles[node.start..node.start + node.count] {
+				if let Some(d) = intersect(t, origin, dir) {
+					if d < closest {
+						closest = d;
+						found = true;
+						if any {
+							return Some(d);
+						}
+					}
+				}
+			}
+		}
+
+		if found { Some(closest) } else { None }
+	}
+}
+
+// whether the ray enters the box before `max_distance`
+fn slab_test(min: Vector3<f32>, max: Vector3<f32>, origin: Vector3<f32>, inv: Vector3<f32>, max_distance: f32) -> bool {
+	let (mut near, mut far) = (0.0f32, max_distance);
+	for axis in 0..3 {
+		let t1 = (min[axis] - origin[axis]) * inv[axis];
+		let t2 = (max[axis] - origin[axis]) * inv[axis];
+		near = near.max(t1.min(t2));
+		far = far.min(t1.max(t2));
+	}
+	near <= far
+}
+
+// Möller–Trumbore, both sides of the triangle are hit
+fn intersect(t: &[Vector3<f32>; 3], origin: Vector3<f32>, dir: Vector3<f32>) -> Option<f32> {
+	let (e1, e2) = (t[1] - t[0], t[2] - t[0]);
+	let p = dir.cross(e2);
+	let det = e1.dot(p);
+	if det.abs() < 1e-9 {
+		return None;
+	}
+
+	let s = origin - t[0];
+	let u = s.dot(p) / det;
+	if !(0.0..=1.0).contains(&u) {
+		return None;
+	}
+	let q = s.cross(e1);
+	let v = dir.dot(q) / det;
+	if v < 0.0 || u + v > 1.0 {
+		return None;
+	}
+
+	let d = e2.dot(q) / det;
+	if d > 1e-5 { Some(d) } else { None }
+}
@@ -0,0 +1,240 @@
+use std::{fs::File, io::{self, Write, BufWriter}};
+
+use crate::{vertex::{Vertex, Attribute}, layers::{InstanceSet, Polyline}};
+
+// the vertex's color darkened by the baked ambient occlusion
+fn shaded_color(v: &Vertex) -> [f32; 4] {
+	let light = 1.0 - v.get(Attribute::Occlusion).clamp(0.0, 1.0);
+	[v.color[0] * light, v.color[1] * light, v.color[2] * light, v.color[3]]
+}
+
+// vertex colors are written using the common `v x y z r g b` extension, only if any vertex has a color or occlusion set
+pub fn obj(path: &str, verts: &[Vertex], inds: &[u32]) -> io::Result<()> {
+	let mut file = BufWriter::new(File::create(path)?);
+	let colored = verts.iter().any(|v| v.color[3] > 0.0 || v.get(Attribute::Occlusion) > 0.0);
+
+	writeln!(file, "# vertices")?;
+	for v in verts {
+		if colored {
+			let c = shaded_color(v);
+			writeln!(file, "v {} {} {} {} {} {}", v.pos[0], v.pos[1], v.pos[2], c[0], c[1], c[2])?;
+		}
+		else {
+			writeln!(file, "v {} {} {}", v.pos[0], v.pos[1], v.pos[2])?;
+		}
+	}
+	writeln!(file, "\n# normals")?;
+	for v in verts {
+		writeln!(file, "vn {} {} {}", v.normal[0], v.normal[1], v.normal[2])?;
+	}
+
+	writeln!(file, "\n# faces")?;
+	for i in 0..inds.len()/3 {
+		let (i1, i2, i3) = (inds[3*i]+1, inds[3*i+1]+1, inds[3*i+2]+1);
+		writeln!(file, "f {i1}//{i1} {i2}//{i2} {i3}//{i3}")?;
+	}
+
+	file.flush()
+}
+
+// ascii ply, every named attribute channel is written as a float vertex property
+pub fn ply(path: &str, verts: &[Vertex], inds: &[u32]) -> io::Result<()> {
+	let mut file = BufWriter::new(File::create(path)?);
+
+	writeln!(file, "ply")?;
+	writeln!(file, "format ascii 1.0")?;
+	writeln!(file, "element vertex {}", verts.len())?;
+	for p in ["x", "y", "z", "nx", "ny", "nz"] {
+		writeln!(file, "property float {p}")?;
+	}
+	for p in ["red", "green", "blue"] {
+		writeln!(file, "property uchar {p}")?;
+	}
+	// the color's alpha is how much it replaces the material, not its opacity
+	writeln!(file, "property float color_weight")?;
+	for a in Attribute::ALL {
+		writeln!(file, "property float {}", a.name())?;
+	}
+	writeln!(file, "element face {}", inds.len() / 3)?;
+	writeln!(file, "property list uchar uint vertex_indices")?;
+	writeln!(file, "end_header")?;
+
+	// the normals point down in this program, they're written pointing up
+	for v in verts {
+		write!(file, "{} {} {} {} {} {}", v.pos[0], v.pos[1], v.pos[2], -v.normal[0], -v.normal[1], -v.normal[2])?;
+		for c in &shaded_color(v)[..3] {
+			write!(file, " {}", (c.clamp(0.0, 1.0) * 255.0).round() as u8)?;
+		}
+		write!(file, " {}", v.color[3])?;
+		for a in Attribute::ALL {
+			write!(file, " {}", v.get(a))?;
+		}
+		writeln!(file)?;
+	}
+
+	for i in 0..inds.len()/3 {
+		writeln!(file, "3 {} {} {}", inds[3*i], inds[3*i+1], inds[3*i+2])?;
+	}
+
+	file.flush()
+}
+
+// binary gltf with positions, normals, the shaded colors and indices in one buffer. The triangles face down
+// and the normals point down in this program, gltf expects counter-clockwise front faces, so both are flipped
+pub fn glb(path: &str, verts: &[Vertex], inds: &[u32]) -> io::Result<()> {
+	const ARRAY_BUFFER: u32 = 34962;
+	const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+	const FLOAT: u32 = 5126;
+	const UNSIGNED_INT: u32 = 5125;
+
+	let mut min = [f32::MAX; 3];
+	let mut max = [f32::MIN; 3];
+	let mut bin = Vec::new();
+	for v in verts {
+		for k in 0..3 {
+			min[k] = min[k].min(v.pos[k]);
+			max[k] = max[k].max(v.pos[k]);
+			bin.extend(v.pos[k].to_le_bytes());
+		}
+	}
+	if verts.is_empty() {
+		(min, max) = ([0.0; 3], [0.0; 3]);
+	}
+	let normals = bin.len();
+	for v in verts {
+		let length = (v.normal[0].powi(2) + v.normal[1].powi(2) + v.normal[2].powi(2)).sqrt();
+		let n = if length > 0.0 { [-v.normal[0] / length, -v.normal[1] / length, -v.normal[2] / length] } else { [0.0, 1.0, 0.0] };
+		for c in n {
+			bin.extend(c.to_le_bytes());
+		}
+	}
+	let colors = bin.len();
+	for v in verts {
+		for c in &shaded_color(v)[..3] {
+			bin.extend(c.clamp(0.0, 1.0).to_le_bytes());
+		}
+	}
+	let indices = bin.len();
+	for t in inds.chunks_exact(3) {
+		for i in [t[0], t[2], t[1]] {
+			bin.extend(i.to_le_bytes());
+		}
+	}
+
+	let n = verts.len();
+	let mut json = format!(concat!(
+		r#"{{"asset":{{"version":"2.0","generator":"fractal_terrain"}},"scene":0,"scenes":[{{"nodes":[0]}}],"nodes":[{{"mesh":0}}],"#,
+		r#""meshes":[{{"primitives":[{{"attributes":{{"POSITION":0,"NORMAL":1,"COLOR_0":2}},"indices":3,"mode":4}}]}}],"#,
+		r#""buffers":[{{"byteLength":{}}}],"bufferViews":["#,
+		r#"{{"buffer":0,"byteOffset":0,"byteLength":{},"target":{}}},"#,
+		r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}},"#,
+		r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}},"#,
+		r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}],"accessors":["#,
+		r#"{{"bufferView":0,"componentType":{},"count":{},"type":"VEC3","min":[{},{},{}],"max":[{},{},{}]}},"#,
+		r#"{{"bufferView":1,"componentType":{},"count":{},"type":"VEC3"}},"#,
+		r#"{{"bufferView":2,"componentType":{},"count":{},"type":"VEC3"}},"#,
+		r#"{{"bufferView":3,"componentType":{},"count":{},"type":"SCALAR"}}]}}"#),
+		bin.len(),
+		normals, ARRAY_BUFFER,
+		normals, colors - normals, ARRAY_BUFFER,
+		colors, indices - colors, ARRAY_BUFFER,
+		indices, bin.len() - indices, ELEMENT_ARRAY_BUFFER,
+		FLOAT, n, min[0], min[1], min[2], max[0], max[1], max[2],
+		FLOAT, n,
+		FLOAT, n,
+		UNSIGNED_INT, inds.len() / 3 * 3);
+
+	// chunks are padded to 4 bytes, json with spaces
+	while json.len() % 4 != 0 {
+		json.push(' ');
+	}
+
+	let mut file = BufWriter::new(File::create(path)?);
+	file.write_all(b"glTF")?;
+	file.write_all(&2u32.to_le_bytes())?;
+	file.write_all(&((12 + 8 + json.len() + 8 + bin.len()) as u32).to_le_bytes())?;
+	file.write_all(&(json.len() as u32).to_le_bytes())?;
+	file.write_all(b"JSON")?;
+	file.write_all(json.as_bytes())?;
+	file.write_all(&(bin.len() as u32).to_le_bytes())?;
+	file.write_all(b"BIN\0")?;
+	file.write_all(&bin)?;
+
+	file.flush()
+}
+
+// rotation is in radians around the normal
+// a json string literal, quoted and escaped
+fn json_string(s: &str) -> String {
+	let mut ret = String::from("\"");
+	for c in s.chars() {
+		match c {
+			'"' => ret.push_str("\\\""),
+			'\\' => ret.push_str("\\\\"),
+			'\n' => ret.push_str("\\n"),
+			'\r' => ret.push_str("\\r"),
+			'\t' => ret.push_str("\\t"),
+			c if (c as u32) < 0x20 => ret.push_str(&format!("\\u{:04x}", c as u32)),
+			c => ret.push(c),
+		}
+	}
+	ret.push('"');
+	ret
+}
+
+// a csv field, quoted if it contains a separator, a quote or a line break
+fn csv_field(s: &str) -> String {
+	if s.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", s.replace('"', "\"\""))
+	}
+	else {
+		s.to_string()
+	}
+}
+
+pub fn instances_json(path: &str, sets: &[InstanceSet]) -> io::Result<()> {
+	let mut file = BufWriter::new(File::create(path)?);
+
+	writeln!(file, "[")?;
+	for (s, set) in sets.iter().enumerate() {
+		writeln!(file, "\t{{ \"name\": {}, \"instances\": [", json_string(&set.name))?;
+		for (i, inst) in set.instances.iter().enumerate() {
+			let (p, n) = (inst.pos, inst.normal);
+			write!(file, "\t\t{{ \"position\": [{}, {}, {}], \"normal\": [{}, {}, {}], \"rotation\": {}, \"scale\": {} }}",
+				p[0], p[1], p[2], n[0], n[1], n[2], inst.rotation, inst.scale)?;
+			writeln!(file, "{}", if i + 1 < set.instances.len() { "," } else { "" })?;
+		}
+		writeln!(file, "\t] }}{}", if s + 1 < sets.len() { "," } else { "" })?;
+	}
+	writeln!(file, "]")?;
+
+	file.flush()
+}
+
+pub fn instances_csv(path: &str, sets: &[InstanceSet]) -> io::Result<()> {
+	let mut file = BufWriter::new(File::create(path)?);
+
+	writeln!(file, "set,x,y,z,nx,ny,nz,rotation,scale")?;
+	for set in sets {
+		for inst in &set.instances {
+			let (p, n) = (inst.pos, inst.normal);
+			writeln!(file, "{},{},{},{},{},{},{},{},{}", csv_field(&set.name), p[0], p[1], p[2], n[0], n[1], n[2], inst.rotation, inst.scale)?;
+		}
+	}
+
+	file.flush()
+}
+
+pub fn polylines_json(path: &str, polylines: &[Polyline]) -> io::Result<()> {
+	let mut file = BufWriter::new(File::create(path)?);
+
+	writeln!(file, "[")?;
+	for (l, line) in polylines.iter().enumerate() {
+		let points: Vec<String> = line.points.iter().map(|p| format!("[{}, {}, {}]", p[0], p[1], p[2])).collect();
+		write!(file, "\t{{ \"name\": {}, \"points\": [{}] }}", json_string(&line.name), points.join(", "))?;
+		writeln!(file, "{}", if l + 1 < polylines.len() { "," } else { "" })?;
+	}
+	writeln!(file, "]")?;
+
+	file.flush()
+}
@@ -0,0 +1,170 @@
+use noise::{NoiseFn, Simplex};
+
+use crate::vertex::{Vertex, Attribute};
+
+// Data produced by operations alongside the mesh, exported separately and drawn as markers in the viewport,
+// and the settings of the whole stack, which the operations read
+
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Instance {
+	pub pos: [f32; 3],
+	pub normal: [f32; 3],
+	pub rotation: f32,	// radians, around the normal
+	pub scale: f32,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct InstanceSet {
+	pub name: String,
+	pub instances: Vec<Instance>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Polyline {
+	pub name: String,
+	pub points: Vec<[f32; 3]>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Stratum {
+	pub name: String,
+	pub thickness: f32,
+	pub hardness: f32,	// 0 - erodes normally, 1 - doesn't erode at all
+	pub color: [f32; 4],
+}
+
+impl Stratum {
+	pub fn new(name: &str, thickness: f32, hardness: f32, color: [f32; 4]) -> Self {
+		Self { name: name.to_string(), thickness, hardness, color }
+	}
+
+	// layered canyon walls
+	pub fn defaults() -> Vec<Stratum> {
+		vec![
+			Stratum::new("schist", 0.15, 0.9, [0.3, 0.25, 0.25, 1.0]),
+			Stratum::new("limestone", 0.1, 0.7, [0.8, 0.75, 0.65, 1.0]),
+			Stratum::new("shale", 0.06, 0.2, [0.6, 0.4, 0.3, 1.0]),
+			Stratum::new("sandstone", 0.08, 0.5, [0.85, 0.55, 0.35, 1.0]),
+			Stratum::new("mudstone", 0.05, 0.15, [0.7, 0.45, 0.4, 1.0]),
+		]
+	}
+}
+
+// Rock layers stacked from `base` upwards, repeating in both directions, tilted by a plane and folded by noise.
+// Kept for the operations after the Strata operation, so that erosion can follow the layers it cuts into.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Strata {
+	pub strata: Vec<Stratum>,
+	pub base: f32,
+	pub tilt: [f32; 2],	// height change per unit along x, z
+	pub fold: f32,
+	pub fold_scale: f32,
+	pub seed: u32,
+}
+
+impl Strata {
+	pub fn noise(&self) -> Simplex {
+		Simplex::new(self.seed)
+	}
+
+	// index of the layer the point lies in
+	pub fn layer_at(&self, noise: &Simplex, pos: [f32; 4]) -> Option<usize> {
+		let total: f32 = self.strata.iter().map(|s| s.thickness.max(0.0)).sum();
+		if total <= 0.0 {
+			return None;
+		}
+
+		// sampled in 3D, the 2D simplex noise has seams
+		let fold = if self.fold_scale > 0.0 {
+			noise.get([(pos[0] / self.fold_scale) as f64, (pos[2] / self.fold_scale) as f64, 0.0]) as f32 * self.fold
+		}
+		else {
+			0.0
+		};
+		let mut depth = (pos[1] - self.base - self.tilt[0] * pos[0] - self.tilt[1] * pos[2] - fold).rem_euclid(total);
+
+		for (i, s) in self.strata.iter().enumerate() {
+			if depth < s.thickness {
+				return Some(i);
+			}
+			depth -= s.thickness.max(0.0);
+		}
+		Some(self.strata.len() - 1)
+	}
+
+	pub fn hardness_at(&self, noise: &Simplex, pos: [f32; 4]) -> f32 {
+		self.layer_at(noise, pos).map_or(0.0, |i| self.strata[i].hardness.clamp(0.0, 1.0))
+	}
+
+	// colors every vertex by the layer it exposes
+	pub fn paint(&self, verts: &mut [Vertex]) {
+		let noise = self.noise();
+		for v in verts {
+			match self.layer_at(&noise, v.pos) {
+				Some(i) => {
+					v.set(Attribute::Stratum, i as f32);
+					v.color = self.strata[i].color;
+				}
+				None => v.set(Attribute::Stratum, -1.0),
+			}
+		}
+	}
+}
+
+// One chunk of a world made of adjacent square grids, addressed by its integer coordinates.
+// The grid is generated `padding` cells larger on every side and cropped afterwards,
+// so that neighbour dependent operations see the same surroundings on both sides of a border.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Chunk {
+	pub x: i32,
+	pub z: i32,
+	pub padding: u32,
+	pub area: Option<[[f32; 2]; 2]>,	// minimum and maximum X and Z of the chunk without its padding, set by Add Square Grid
+}
+
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Layers {
+	pub tiling: bool,	// the terrain repeats along X and Z, see mesh_operation::Tiles
+	pub chunk: Option<Chunk>,	// the stack generates a single chunk of a bigger world
+	pub instance_sets: Vec<InstanceSet>,
+	pub polylines: Vec<Polyline>,
+	pub strata: Option<Strata>,
+	pub walkable: (Vec<Vertex>, Vec<u32>),	// the walkable triangles, as a mesh of their own
+	pub reports: Vec<String>,				// results of analyses, shown in the left panel
+	pub skirt: Option<f32>,					// depth of the skirt added around the finished mesh, see mesh_operation::skirt
+}
+
+impl Layers {
+	// line list geometry: a stem along the normal and a cross at its base for every instance, and the polylines
+	pub fn markers(&self) -> Vec<Vertex> {
+		const INSTANCE_COLOR: [f32; 4] = [1.0, 0.9, 0.1, 1.0];
+		const POLYLINE_COLOR: [f32; 4] = [1.0, 0.4, 0.1, 1.0];
+		let mut lines = Vec::new();
+
+		let mut line = |a: [f32; 3], b: [f32; 3], color: [f32; 4]| {
+			for p in [a, b] {
+				let mut v = Vertex::new(p[0], p[1], p[2], 0.0, 1.0, 0.0);
+				v.color = color;
+				lines.push(v);
+			}
+		};
+
+		for set in &self.instance_sets {
+			for i in &set.instances {
+				let (p, n, s) = (i.pos, i.normal, i.scale * 0.2);
+				line(p, [p[0] + n[0] * s, p[1] + n[1] * s, p[2] + n[2] * s], INSTANCE_COLOR);
+				line([p[0] - s * 0.3, p[1], p[2]], [p[0] + s * 0.3, p[1], p[2]], INSTANCE_COLOR);
+				line([p[0], p[1], p[2] - s * 0.3], [p[0], p[1], p[2] + s * 0.3], INSTANCE_COLOR);
+			}
+		}
+
+		// lifted a bit, so that they aren't hidden in the surface they lie on
+		for polyline in &self.polylines {
+			for w in polyline.points.windows(2) {
+				line([w[0][0], w[0][1] + 0.02, w[0][2]], [w[1][0], w[1][1] + 0.02, w[1][2]], POLYLINE_COLOR);
+			}
+		}
+
+		lines
+	}
+}
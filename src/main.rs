@@ -4,6 +4,9 @@ mod vertex;
 mod camera;
 mod mesh_operation;
 mod view;
+mod export;
+mod layers;
+mod bvh;
 
 use app::App;
 use eframe::epaint::vec2;
@@ -1,10 +1,10 @@
-use std::{ops::RangeInclusive, collections::{HashMap, HashSet}};
+use std::{ops::RangeInclusive, collections::{HashMap, HashSet, BinaryHeap}, cmp::Ordering};
 
-use cgmath::Vector4;
+use cgmath::{Vector4, InnerSpace};
 use eframe::egui::{self, Ui, WidgetText};
 use rand::{rngs::StdRng, RngCore};
 
-use crate::vertex::Vertex;
+use crate::{vertex::{Vertex, Attribute}, bvh::Bvh, layers::{Layers, Instance, InstanceSet, Polyline, Stratum, Strata}};
 
 fn v_label_drag(ui: &mut Ui, label: Option<impl Into<WidgetText>>, n: &mut impl egui::emath::Numeric, range: RangeInclusive<impl egui::emath::Numeric>, speed: f32) {
 	ui.vertical(|ui| {
@@ -15,8 +15,62 @@ fn v_label_drag(ui: &mut Ui, label: Option<impl Into<WidgetText>>, n: &mut impl
 	});
 }
 
+// uniformly distributed in [0, 1]
+fn unit(random: &mut StdRng) -> f32 {
+	random.next_u32() as f32 / u32::MAX as f32
+}
+
+// a value in [0, 1] that only depends on the position on X and Z, the same in every chunk
+fn world_random(pos: [f32; 4], seed: u32) -> f32 {
+	let (x, z) = ((pos[0] * 1024.0).round() as i32, (pos[2] * 1024.0).round() as i32);
+	cell_hash(x, z, seed) as f32 / u32::MAX as f32
+}
+
+// deterministic pseudo-random number for integer coordinates
+fn cell_hash(x: i32, z: i32, seed: u32) -> u32 {
+	let mut h = seed ^ (x as u32).wrapping_mul(0x27d4_eb2d) ^ (z as u32).wrapping_mul(0x1656_67b1);
+	h = (h ^ (h >> 15)).wrapping_mul(0x85eb_ca6b);
+	h = (h ^ (h >> 13)).wrapping_mul(0xc2b2_ae35);
+	h ^ (h >> 16)
+}
+
 // todo: Erosion, Unsubdivide, Fractal
 
+// a biome is assigned to the first rule whose ranges contain the vertex
+#[derive(PartialEq, Clone, Debug)]
+pub struct BiomeRule {
+	pub name: String,
+	pub color: [f32; 4],
+	pub material: Attribute,
+	pub height: [f32; 2],		// min, max
+	pub slope: [f32; 2],		// min, max, 0 - flat, 1 - vertical
+	pub moisture: [f32; 2],		// min, max
+	pub insolation: [f32; 2],	// min, max
+}
+
+impl BiomeRule {
+	fn new(name: &str, color: [f32; 4], material: Attribute, height: [f32; 2], slope: [f32; 2], moisture: [f32; 2]) -> Self {
+		Self { name: name.to_string(), color, material, height, slope, moisture, insolation: [0.0, 1.0] }
+	}
+
+	fn matches(&self, height: f32, slope: f32, moisture: f32, insolation: f32) -> bool {
+		self.height[0] <= height && height <= self.height[1] &&
+		self.slope[0] <= slope && slope <= self.slope[1] &&
+		self.moisture[0] <= moisture && moisture <= self.moisture[1] &&
+		self.insolation[0] <= insolation && insolation <= self.insolation[1]
+	}
+
+	pub fn defaults() -> Vec<BiomeRule> {
+		vec![
+			BiomeRule::new("snow", [0.95, 0.95, 1.0, 0.0], Attribute::Snow, [1.2, 100.0], [0.0, 0.6], [0.0, 1.0]),
+			BiomeRule::new("beach", [0.85, 0.8, 0.55, 0.0], Attribute::Sand, [-100.0, -0.8], [0.0, 0.3], [0.0, 1.0]),
+			BiomeRule::new("grassland", [0.1, 0.7, 0.3, 0.0], Attribute::Grass, [-100.0, 0.8], [0.0, 0.35], [0.0, 0.5]),
+			BiomeRule::new("forest", [0.05, 0.3, 0.1, 0.8], Attribute::Grass, [-100.0, 1.2], [0.0, 0.5], [0.5, 1.0]),
+			BiomeRule::new("rock", [0.2, 0.2, 0.2, 0.0], Attribute::Rock, [-100.0, 100.0], [0.0, 1.0], [0.0, 1.0]),
+		]
+	}
+}
+
 pub enum ShowResponse {
 	None,
 	Delete,
@@ -28,7 +82,7 @@ pub enum ShowResponse {
 // The two variants beginning with '_':
 // Subdivide smooth - not implemented in the end, because a simmilar effect can be achieved by first subdividing, then smoothing
 // MergeCleanup - it is no longer needed, since Sybdivide has been rewritten and no longer needs a cleanup afterwards
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Clone, Debug)]
 pub enum MeshOperation {
 	AddTriangle(f32),							// size
 	AddTriSquare(f32),							// size
@@ -41,14 +95,41 @@ pub enum MeshOperation {
 	DisplaceRandom(f32, [bool; 3]),				// amount, axes
 	DisplaceSmooth(f32, f32, u32, [bool; 3]),	// amount, scale, octaves, axes
 	Smooth(f32, u32),							// amount, iterations
+	Sharpen(f32, u32, f32, bool),				// amount, iterations, limit, only Y
 
 	_MergeCleanup,
 
 	FractalTerrain(u32, f32, f32),				// iterations, displacement start, displacement decay
+	SpectralSynthesis(f32, f32, u32, f32, f32),	// amount, beta, resolution, anisotropy, anisotropy angle
+	DlaRidges(f32, u32, f32, u32),				// amount, resolution, density, blur passes
+	CellularIslands(u32, f32, String, u32, f32),	// resolution, fill probability, rule, iterations, height
+
+	Biomes(f32, f32, Vec<BiomeRule>),			// moisture amount, moisture scale, rules
+	Strata(f32, [f32; 2], f32, f32, Vec<Stratum>),	// base height, tilt (x, z), fold amount, fold scale, layers from the bottom up
+	Accumulate(Attribute, [f32; 2], f32, f32, f32, f32, f32),	// snow or sediment, height range (none, full), max slope, wind direction (degrees), wind effect, shelter effect, depth
+	Scatter(String, f32, f32, [f32; 2], [f32; 2]),	// name, spacing, max slope, height range, scale range
+
+	CarveRoad(Vec<[f32; 2]>, f32, f32, u32),	// control points (x, z), width, shoulder width, grade smoothing
+	RoadNetwork(u32, f32, f32, f32, f32, f32),	// sites, site spacing, slope penalty, water level, width, shoulder width
+
+	Plateau(f32, f32, f32, f32, [f32; 2]),		// cell size, coverage, radius, sharpness, height range
+	FaultFormation(u32, f32, f32, f32),			// iterations, displacement start, displacement end, falloff
+	ParticleDeposition(u32, u32, f32, f32, f32),	// particles, emitters, emitter drift, particle height, repose slope
+	WindErosion(f32, f32, u32, u32, f32),		// direction (degrees), strength, iterations, hop length, repose slope
+	GlacialCarve(f32, f32, f32),				// equilibrium-line altitude, carving strength, valley width
+
+	ImproveTriangles(u32, bool, f32),			// passes, delaunay (otherwise shortest diagonal), ridge alignment
+	Remesh(f32, u32),							// target edge length, iterations
+	Skirt(f32),									// depth
+
+	AmbientOcclusion(u32, f32),					// rays, max distance
+	Insolation(f32, bool, u32, u32, f32),		// latitude (degrees), whole year, day of year, samples per day, max distance
+	Viewshed([f32; 2], f32, f32),				// observer (x, z), observer height, range
+	Walkability(f32, f32, [f32; 2]),			// max slope, min clearance, spawn (x, z)
 }
 
 impl MeshOperation {
-	pub const DEFAULTS: [MeshOperation; 9] = [
+	pub fn defaults() -> [MeshOperation; 31] { [
 		MeshOperation::AddTriangle(5.0),
 		MeshOperation::AddTriSquare(5.0),
 		MeshOperation::AddTriangleGrid(10.0, 20),
@@ -58,9 +139,44 @@ impl MeshOperation {
 		MeshOperation::DisplaceRandom(0.2, [false, true, false]),
 		MeshOperation::DisplaceSmooth(1.0, 1.0, 1, [false, true, false]),
 		MeshOperation::Smooth(0.5, 1),
+		MeshOperation::Sharpen(0.5, 1, 0.1, true),
 		// MeshOperation::MergeCleanup,
 		MeshOperation::FractalTerrain(6, 2.0, 2.0),
-	];
+		MeshOperation::SpectralSynthesis(1.0, 1.5, 256, 1.0, 0.0),
+		MeshOperation::DlaRidges(2.0, 96, 0.15, 6),
+		MeshOperation::CellularIslands(64, 0.45, "B5678/S45678".to_string(), 5, 0.5),
+		MeshOperation::Biomes(1.0, 3.0, BiomeRule::defaults()),
+		MeshOperation::Strata(-1.0, [0.0, 0.0], 0.05, 2.0, Stratum::defaults()),
+		MeshOperation::Accumulate(Attribute::Snow, [0.8, 1.4], 0.5, 0.0, 0.5, 1.0, 0.0),
+		MeshOperation::Scatter("trees".to_string(), 0.3, 0.3, [-100.0, 100.0], [0.8, 1.2]),
+		MeshOperation::CarveRoad(vec![[-4.0, -3.0], [-1.0, 1.0], [2.0, -1.0], [4.0, 3.0]], 0.4, 0.4, 20),
+		MeshOperation::RoadNetwork(6, 2.0, 20.0, -0.5, 0.3, 0.3),
+		MeshOperation::Plateau(3.0, 0.5, 0.5, 0.8, [0.5, 1.5]),
+		MeshOperation::FaultFormation(200, 0.15, 0.01, 0.2),
+		MeshOperation::ParticleDeposition(20000, 3, 0.05, 0.01, 0.5),
+		MeshOperation::WindErosion(0.0, 0.005, 20, 3, 0.65),
+		MeshOperation::GlacialCarve(0.3, 0.4, 1.0),
+		MeshOperation::ImproveTriangles(5, true, 0.0),
+		MeshOperation::Remesh(0.2, 3),
+		MeshOperation::Skirt(0.5),
+		MeshOperation::AmbientOcclusion(32, 2.0),
+		MeshOperation::Insolation(45.0, true, 172, 24, 5.0),
+		MeshOperation::Viewshed([0.0, 0.0], 0.1, 0.0),
+		MeshOperation::Walkability(0.3, 0.2, [0.0, 0.0]),
+	] }
+
+	// whole stacks of operations, which replace the current one
+	pub fn presets() -> Vec<(&'static str, Vec<MeshOperation>)> {
+		vec![
+			("Mountain Range", vec![
+				MeshOperation::AddTriSquareGrid(10.0, 120),
+				MeshOperation::DlaRidges(2.5, 96, 0.15, 6),
+				MeshOperation::DisplaceSmooth(0.3, 1.5, 4, [false, true, false]),
+				MeshOperation::Smooth(0.3, 1),
+				MeshOperation::Biomes(1.0, 3.0, BiomeRule::defaults()),
+			]),
+		]
+	}
 	
 	pub fn show(&mut self, ui: &mut egui::Ui, time: f32) -> ShowResponse {
 		let mut ret = ShowResponse::None;
@@ -131,6 +247,14 @@ impl MeshOperation {
 						v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 0.1);
 					});
 				},
+				Self::Sharpen(amount, iterations, limit, only_y) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("amount:"), amount, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("limit"), limit, 0.0..=f32::MAX, 0.01);
+					});
+					ui.checkbox(only_y, "only Y");
+				},
 				Self::_MergeCleanup => {}
 				Self::FractalTerrain(iterations, displacement_start, displacement_decay) => {
 					v_label_drag(ui, Some("iterations"), iterations, 1..=u32::MAX, 0.2);
@@ -139,6 +263,294 @@ impl MeshOperation {
 						v_label_drag(ui, Some("displacement decay"), displacement_decay, 1.0..=f32::MAX, 0.01);
 					});
 				}
+				Self::SpectralSynthesis(amount, beta, resolution, anisotropy, angle) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("amount"), amount, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("beta"), beta, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("resolution"), resolution, 2..=4096, 1.0);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("anisotropy"), anisotropy, 0.01..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("anisotropy angle"), angle, f32::MIN..=f32::MAX, 1.0);
+					});
+				}
+				Self::DlaRidges(amount, resolution, density, passes) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("amount"), amount, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("resolution"), resolution, 2..=1024, 1.0);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("density"), density, 0.0..=1.0, 0.01);
+						v_label_drag(ui, Some("blur passes"), passes, 0..=u32::MAX, 0.1);
+					});
+				}
+				Self::CellularIslands(resolution, fill, rule, iterations, height) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("resolution"), resolution, 2..=1024, 1.0);
+						v_label_drag(ui, Some("fill probability"), fill, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("height"), height, 0.0..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						ui.label("rule:");
+						ui.text_edit_singleline(rule);
+					});
+				}
+				Self::Biomes(moisture_amount, moisture_scale, rules) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("moisture amount"), moisture_amount, 0.0..=1.0, 0.01);
+						v_label_drag(ui, Some("moisture scale"), moisture_scale, 0.0..=f32::MAX, 0.01);
+					});
+
+					let mut delete = None;
+					for (i, rule) in rules.iter_mut().enumerate() {
+						ui.push_id(i, |ui| {
+							egui::CollapsingHeader::new(rule.name.clone()).id_source("rule").show(ui, |ui| {
+								ui.horizontal(|ui| {
+									ui.text_edit_singleline(&mut rule.name);
+									ui.color_edit_button_rgba_unmultiplied(&mut rule.color);
+								});
+								egui::ComboBox::from_id_source("material").selected_text(rule.material.name()).show_ui(ui, |ui| {
+									for m in [Attribute::Grass, Attribute::Rock, Attribute::Sand, Attribute::Snow] {
+										ui.selectable_value(&mut rule.material, m, m.name());
+									}
+								});
+								for (label, range) in [("height", &mut rule.height), ("slope", &mut rule.slope), ("moisture", &mut rule.moisture), ("insolation", &mut rule.insolation)] {
+									ui.horizontal(|ui| {
+										v_label_drag(ui, Some(format!("{label} min")), &mut range[0], f32::MIN..=f32::MAX, 0.01);
+										v_label_drag(ui, Some(format!("{label} max")), &mut range[1], f32::MIN..=f32::MAX, 0.01);
+									});
+								}
+								if ui.button("delete rule").clicked() { delete = Some(i) }
+							});
+						});
+					}
+					if let Some(i) = delete {
+						rules.remove(i);
+					}
+					if ui.button("add rule").clicked() {
+						rules.push(BiomeRule::new("new biome", [1.0, 1.0, 1.0, 0.0], Attribute::Grass, [-100.0, 100.0], [0.0, 1.0], [0.0, 1.0]));
+					}
+				}
+				Self::Strata(base, tilt, fold, fold_scale, strata) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("base height"), base, f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("tilt x"), &mut tilt[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("tilt z"), &mut tilt[1], f32::MIN..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("fold amount"), fold, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("fold scale"), fold_scale, 0.0..=f32::MAX, 0.01);
+					});
+
+					let mut delete = None;
+					for (i, stratum) in strata.iter_mut().enumerate() {
+						ui.push_id(i, |ui| {
+							egui::CollapsingHeader::new(stratum.name.clone()).id_source("stratum").show(ui, |ui| {
+								ui.horizontal(|ui| {
+									ui.text_edit_singleline(&mut stratum.name);
+									ui.color_edit_button_rgba_unmultiplied(&mut stratum.color);
+								});
+								ui.horizontal(|ui| {
+									v_label_drag(ui, Some("thickness"), &mut stratum.thickness, 0.0..=f32::MAX, 0.01);
+									v_label_drag(ui, Some("hardness"), &mut stratum.hardness, 0.0..=1.0, 0.01);
+								});
+								if ui.button("delete layer").clicked() { delete = Some(i) }
+							});
+						});
+					}
+					if let Some(i) = delete {
+						strata.remove(i);
+					}
+					if ui.button("add layer").clicked() {
+						strata.push(Stratum::new("new layer", 0.1, 0.5, [1.0, 1.0, 1.0, 1.0]));
+					}
+				}
+				Self::Accumulate(target, height, max_slope, direction, wind, shelter, depth) => {
+					egui::ComboBox::from_id_source("target").selected_text(target.name()).show_ui(ui, |ui| {
+						for a in [Attribute::Snow, Attribute::Sediment] {
+							ui.selectable_value(target, a, a.name());
+						}
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("height none"), &mut height[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("height full"), &mut height[1], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("max slope"), max_slope, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("wind direction"), direction, 0.0..=360.0, 1.0);
+						v_label_drag(ui, Some("wind effect"), wind, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("shelter effect"), shelter, 0.0..=f32::MAX, 0.01);
+					});
+					v_label_drag(ui, Some("depth"), depth, 0.0..=f32::MAX, 0.001);
+				}
+				Self::Scatter(name, spacing, max_slope, height, scale) => {
+					ui.horizontal(|ui| {
+						ui.label("name:");
+						ui.text_edit_singleline(name);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("spacing"), spacing, 0.01..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("max slope"), max_slope, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("height min"), &mut height[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("height max"), &mut height[1], f32::MIN..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("scale min"), &mut scale[0], 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("scale max"), &mut scale[1], 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::CarveRoad(points, width, shoulder, smoothing) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("width"), width, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("shoulder"), shoulder, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("grade smoothing"), smoothing, 0..=u32::MAX, 0.2);
+					});
+
+					ui.label("control points (x, z):");
+					let mut delete = None;
+					for (i, p) in points.iter_mut().enumerate() {
+						ui.horizontal(|ui| {
+							ui.add(egui::DragValue::new(&mut p[0]).speed(0.01));
+							ui.add(egui::DragValue::new(&mut p[1]).speed(0.01));
+							if ui.button("x").clicked() { delete = Some(i) }
+						});
+					}
+					if let Some(i) = delete {
+						points.remove(i);
+					}
+					if ui.button("add point").clicked() {
+						let last = points.last().copied().unwrap_or([0.0, 0.0]);
+						points.push([last[0] + 1.0, last[1]]);
+					}
+				}
+				Self::RoadNetwork(sites, spacing, slope_penalty, water_level, width, shoulder) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("sites"), sites, 2..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("site spacing"), spacing, 0.0..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("slope penalty"), slope_penalty, 0.0..=f32::MAX, 0.1);
+						v_label_drag(ui, Some("water level"), water_level, f32::MIN..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("width"), width, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("shoulder"), shoulder, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::Plateau(cell, coverage, radius, sharpness, height) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("cell size"), cell, 0.01..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("coverage"), coverage, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("radius"), radius, 0.0..=1.0, 0.01);
+						v_label_drag(ui, Some("sharpness"), sharpness, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("height min"), &mut height[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("height max"), &mut height[1], f32::MIN..=f32::MAX, 0.01);
+					});
+				}
+				Self::FaultFormation(iterations, displacement_start, displacement_end, falloff) => {
+					v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 1.0);
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("displacement start"), displacement_start, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("displacement end"), displacement_end, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("falloff"), falloff, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::ParticleDeposition(particles, emitters, drift, height, repose) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("particles"), particles, 0..=u32::MAX, 10.0);
+						v_label_drag(ui, Some("emitters"), emitters, 1..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("emitter drift"), drift, 0.0..=1.0, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("particle height"), height, 0.0..=f32::MAX, 0.001);
+						v_label_drag(ui, Some("repose slope"), repose, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::WindErosion(direction, strength, iterations, hop, repose) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("direction"), direction, 0.0..=360.0, 1.0);
+						v_label_drag(ui, Some("strength"), strength, 0.0..=f32::MAX, 0.001);
+						v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 0.1);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("hop length"), hop, 1..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("repose slope"), repose, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::GlacialCarve(ela, strength, width) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("equilibrium line"), ela, f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("strength"), strength, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("valley width"), width, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::ImproveTriangles(passes, delaunay, ridges) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("passes"), passes, 0..=u32::MAX, 0.1);
+						v_label_drag(ui, Some("ridge alignment"), ridges, 0.0..=f32::MAX, 0.001);
+					});
+					ui.horizontal(|ui| {
+						ui.radio_value(delaunay, true, "delaunay");
+						ui.radio_value(delaunay, false, "shortest diagonal");
+					});
+				}
+				Self::Remesh(length, iterations) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("edge length"), length, 0.001..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("iterations"), iterations, 0..=u32::MAX, 0.1);
+					});
+				}
+				Self::Skirt(depth) => {
+					v_label_drag(ui, Some("depth"), depth, 0.0..=f32::MAX, 0.01);
+				}
+				Self::AmbientOcclusion(rays, distance) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("rays"), rays, 1..=u32::MAX, 0.5);
+						v_label_drag(ui, Some("max distance"), distance, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::Insolation(latitude, yearly, day, samples, distance) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("latitude"), latitude, -90.0..=90.0, 0.5);
+						ui.checkbox(yearly, "whole year");
+						if !*yearly {
+							v_label_drag(ui, Some("day of year"), day, 1..=365, 1.0);
+						}
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("samples per day"), samples, 1..=u32::MAX, 0.5);
+						v_label_drag(ui, Some("max distance"), distance, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::Viewshed(observer, height, range) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("observer x"), &mut observer[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("observer z"), &mut observer[1], f32::MIN..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("height"), height, 0.0..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("range"), range, 0.0..=f32::MAX, 0.01);
+					});
+				}
+				Self::Walkability(max_slope, clearance, spawn) => {
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("max slope"), max_slope, 0.0..=1.0, 0.01);
+						v_label_drag(ui, Some("min clearance"), clearance, 0.0..=f32::MAX, 0.01);
+					});
+					ui.horizontal(|ui| {
+						v_label_drag(ui, Some("spawn x"), &mut spawn[0], f32::MIN..=f32::MAX, 0.01);
+						v_label_drag(ui, Some("spawn z"), &mut spawn[1], f32::MIN..=f32::MAX, 0.01);
+					});
+				}
 			}
 
 			ui.label(if time > 0.0 { format!("{:.2}s", time) } else { "".to_string() });
@@ -169,14 +581,49 @@ impl MeshOperation {
 			Self::DisplaceRandom(_, _) => "Displace Random",
 			Self::DisplaceSmooth(_, _, _, _) => "Displace Smooth",
 			Self::Smooth(_, _) => "Smooth",
+			Self::Sharpen(_, _, _, _) => "Sharpen",
 
 			Self::_MergeCleanup => "Merge Cleanup",
 
 			Self::FractalTerrain(_, _, _) => "Fractal Terrain",
+			Self::SpectralSynthesis(_, _, _, _, _) => "Spectral Synthesis",
+			Self::DlaRidges(_, _, _, _) => "DLA Ridges",
+			Self::CellularIslands(_, _, _, _, _) => "Cellular Islands",
+
+			Self::Biomes(_, _, _) => "Biomes",
+			Self::Strata(_, _, _, _, _) => "Strata",
+			Self::Accumulate(_, _, _, _, _, _, _) => "Accumulate",
+			Self::Scatter(_, _, _, _, _) => "Scatter",
+
+			Self::CarveRoad(_, _, _, _) => "Carve Road",
+			Self::RoadNetwork(_, _, _, _, _, _) => "Road Network",
+
+			Self::Plateau(_, _, _, _, _) => "Plateau",
+			Self::FaultFormation(_, _, _, _) => "Fault Formation",
+			Self::ParticleDeposition(_, _, _, _, _) => "Particle Deposition",
+			Self::WindErosion(_, _, _, _, _) => "Wind Erosion",
+			Self::GlacialCarve(_, _, _) => "Glacial Carve",
+
+			Self::ImproveTriangles(_, _, _) => "Improve Triangles",
+			Self::Remesh(_, _) => "Remesh",
+			Self::Skirt(_) => "Skirt",
+			Self::AmbientOcclusion(_, _) => "Ambient Occlusion",
+			Self::Insolation(_, _, _, _, _) => "Insolation",
+			Self::Viewshed(_, _, _) => "Viewshed",
+			Self::Walkability(_, _, _) => "Walkability",
 		}
 	}
 
-	pub fn execute(&self, verts_in: Vec<Vertex>, mut inds_in: Vec<u32>, random: &mut StdRng) -> (Vec<Vertex>, Vec<u32>) {
+	// whether the operation only changes the vertices it's given, keeping their number and order
+	pub fn keeps_vertices(&self) -> bool {
+		!matches!(self,
+			Self::AddTriangle(_) | Self::AddTriSquare(_) | Self::AddTriangleGrid(_, _) | Self::AddTriSquareGrid(_, _) |
+			Self::Subdivide(_) | Self::_SubdivideSmooth(_, _) | Self::_MergeCleanup |
+			Self::FractalTerrain(_, _, _) | Self::Remesh(_, _)
+		)
+	}
+
+	pub fn execute(&self, verts_in: Vec<Vertex>, mut inds_in: Vec<u32>, random: &mut StdRng, layers: &mut Layers) -> (Vec<Vertex>, Vec<u32>) {
 		let mut verts_out = Vec::<Vertex>::new();
 		let mut inds_out = Vec::<u32>::new();
 
@@ -252,24 +699,37 @@ impl MeshOperation {
 
 			Self::AddTriSquareGrid(size, subdivisions) => {
 				let num = subdivisions + 1;
-				for x in 0..num {
-					let x_world = (x as f32 / num as f32 - 0.5) * size;
+				// when tiling, the last row and column are added as well, so that the grid spans exactly one period
+				let mut count = if layers.tiling { num + 1 } else { num };
+				// a chunk includes its last row and column too, plus the padding, and its positions
+				// come from the cell coordinates in the whole world so that they're equal in neighbouring chunks
+				let world = |cell: i32| (cell as f32 / num as f32 - 0.5) * size;
+				let mut first = [0, 0];
+				if let Some(chunk) = layers.chunk.as_mut() {
+					let padding = chunk.padding as i32;
+					let (x, z) = (chunk.x * num as i32, chunk.z * num as i32);
+					count = num + 1 + 2 * chunk.padding;
+					first = [x - padding, z - padding];
+					chunk.area = Some([[world(x), world(z)], [world(x + num as i32), world(z + num as i32)]]);
+				}
+				for x in 0..count {
+					let x_world = world(first[0] + x as i32);
 			
-					for z in 0..num {
-						let z_world = (z as f32 / num as f32 - 0.5) * size;
+					for z in 0..count {
+						let z_world = world(first[1] + z as i32);
 			
 						verts_out.push(Vertex::new(x_world, 0.0, z_world, 0.0, 0.0, 0.0));
 			
 						if x != 0 && z != 0 {
-							let index = x * num + z;
+							let index = x * count + z;
 			
 							inds_out.push(index as u32);
-							inds_out.push((index - num - 1) as u32);
+							inds_out.push((index - count - 1) as u32);
 							inds_out.push((index - 1) as u32);
 			
 							inds_out.push(index as u32);
-							inds_out.push((index - num) as u32);
-							inds_out.push((index - num - 1) as u32);
+							inds_out.push((index - count) as u32);
+							inds_out.push((index - count - 1) as u32);
 						}
 					}
 				}
@@ -278,10 +738,17 @@ impl MeshOperation {
 			Self::DisplaceRandom(amount, axes) => {
 				verts_out = verts_in;
 				inds_out = inds_in;
+				// in a chunk, the displacement depends on the position in the world instead of the vertex order
+				let chunk_seed = layers.chunk.map(|_| random.next_u32());
 				for v in verts_out.iter_mut() {
+					let key = v.pos;
 					for i in 0..axes.len() {	
 						if axes[i] {
-							v.pos[i] += (random.next_u32() as f32 / u32::MAX as f32) * amount;
+							let value = match chunk_seed {
+								Some(seed) => world_random(key, seed.wrapping_add(i as u32)),
+								None => random.next_u32() as f32 / u32::MAX as f32,
+							};
+							v.pos[i] += value * amount;
 						}
 					}
 				}
@@ -294,6 +761,7 @@ impl MeshOperation {
 				use noise::{NoiseFn, Simplex};
 
 				let noise: Vec<_> = (0..*octaves).into_iter().map(|_| Simplex::new(random.next_u32())).collect();
+				let tiles = Tiles::new(&verts_out, layers.tiling);
 				
 				for v in verts_out.iter_mut() {
 					for i in 0..axes.len() {	
@@ -307,10 +775,24 @@ impl MeshOperation {
 							let mut cumulator = 0.0;
 							for o in 0..*octaves {
 								sum += 0.5f32.powi(o as i32);
-								cumulator += 0.5f32.powi(o as i32) * noise[o as usize].get(
-									[pos[0] * 2f64.powi(o as i32), 
-									 pos[1] * 2f64.powi(o as i32), 
-									 pos[2] * 2f64.powi(o as i32)]) as f32;
+								let value = match tiles.period {
+									// the noise is blended with itself one period away on X and Z, so that it repeats with the period
+									// (the 4D simplex noise, which could wrap around circles instead, has seams)
+									Some(period) => {
+										let frequency = 2f64.powi(o as i32);
+										let sample = |x: f32, z: f32| noise[o as usize].get([(x / scale) as f64 * frequency, pos[1] * frequency, (z / scale) as f64 * frequency]);
+										let (x, z) = ((v.pos[0] - tiles.corner[0]).rem_euclid(period), (v.pos[2] - tiles.corner[1]).rem_euclid(period));
+										let weight = |t: f32| (t * t * (3.0 - 2.0 * t)) as f64;
+										let (wx, wz) = (weight(x / period), weight(z / period));
+										(sample(x, z) * (1.0 - wx) + sample(x - period, z) * wx) * (1.0 - wz) +
+										(sample(x, z - period) * (1.0 - wx) + sample(x - period, z - period) * wx) * wz
+									}
+									None => noise[o as usize].get(
+										[pos[0] * 2f64.powi(o as i32), 
+										 pos[1] * 2f64.powi(o as i32), 
+										 pos[2] * 2f64.powi(o as i32)]),
+								};
+								cumulator += 0.5f32.powi(o as i32) * value as f32;
 							}
 							cumulator /= sum;
 							
@@ -444,6 +926,7 @@ impl MeshOperation {
 				
 				inds_out = inds_in;
 				verts_out = verts_in;
+				let tiles = Tiles::new(&verts_out, layers.tiling);
 				for _ in 0..*iterations {
 					let mut verts: Vec<(Vertex, Vector4<f32>)> = verts_out.iter().map(|v| (*v, [0.0, 0.0, 0.0, 0.0].into())).collect();
 					
@@ -456,14 +939,16 @@ impl MeshOperation {
 						let v1 = verts[i1].0;
 						let v2 = verts[i2].0;
 
-						verts[i0].1 += Vector4::from(v1.pos);
-						verts[i0].1 += Vector4::from(v2.pos);
-
-						verts[i1].1 += Vector4::from(v0.pos);
-						verts[i1].1 += Vector4::from(v2.pos);
-
-						verts[i2].1 += Vector4::from(v0.pos);
-						verts[i2].1 += Vector4::from(v1.pos);
+						// when tiling, the neighbors are gathered by the canonical twin, moved next to it
+						let mut gather = |i: usize, a: Vertex, b: Vertex| {
+							let c = tiles.twins[i];
+							let shift = Vector4::from(verts[c].0.pos) - Vector4::from(verts[i].0.pos);
+							verts[c].1 += Vector4::from(a.pos) + shift;
+							verts[c].1 += Vector4::from(b.pos) + shift;
+						};
+						gather(i0, v1, v2);
+						gather(i1, v0, v2);
+						gather(i2, v0, v1);
 					}
 
 					for v in verts.iter_mut() {
@@ -478,6 +963,39 @@ impl MeshOperation {
 					}
 
 					verts_out = verts.iter().map(|v| v.0).collect();
+					tiles.weld(&mut verts_out);
+				}
+			}
+
+			Self::Sharpen(amount, iterations, limit, only_y) => {
+				// the opposite of smooth, each vertex moves away from the average position of its neighbors
+				inds_out = inds_in;
+				verts_out = verts_in;
+				let neighbors = adjacency(&inds_out, verts_out.len());
+
+				for _ in 0..*iterations {
+					let averages: Vec<Option<cgmath::Vector3<f32>>> = neighbors.iter().map(|n| {
+						if n.is_empty() {
+							return None;
+						}
+						let sum: cgmath::Vector3<f32> = n.iter().map(|i| Vector4::from(verts_out[*i as usize].pos).truncate()).sum();
+						Some(sum / n.len() as f32)
+					}).collect();
+
+					for (v, average) in verts_out.iter_mut().zip(averages) {
+						let Some(average) = average else { continue };
+						let pos = Vector4::from(v.pos).truncate();
+						let mut disp = (pos - average) * *amount;
+						if *only_y {
+							disp.x = 0.0;
+							disp.z = 0.0;
+						}
+						// limited, so that the spikes don't run away with more iterations
+						if disp.magnitude() > *limit {
+							disp = disp.normalize() * *limit;
+						}
+						v.pos = (pos + disp).extend(1.0).into();
+					}
 				}
 			}
 
@@ -485,10 +1003,15 @@ impl MeshOperation {
 				// exactly like subdivide, but with vertical displacement
 				
 				verts_out = verts_in;
+				// in a chunk, the displacement depends on the position in the world instead of the triangle order
+				let chunk_seed = layers.chunk.map(|_| random.next_u32());
 				
 				for iteration in 0..*iterations {
 
 					let mut mids = HashMap::<(u32, u32), u32>::new();
+					// when tiling, the twin edges on the opposite edges of the mesh get the same displacement
+					let tiles = Tiles::new(&verts_out, layers.tiling);
+					let mut shared = HashMap::<(usize, usize), f32>::new();
 
 					for i in 0..(inds_in.len()/3) {
 						//       1
@@ -534,7 +1057,12 @@ impl MeshOperation {
 
 								let disp = displacement_start * displacement_decay.powf(-(iteration as f32));
 
-								mid.pos[1] += (random.next_u32() as f32 / u32::MAX as f32 - 0.5) * disp;
+								let (t1, t2) = (tiles.twins[inds[IDS[i][0]] as usize], tiles.twins[inds[IDS[i][1]] as usize]);
+								let offset = match chunk_seed {
+									Some(seed) => world_random(mid.pos, seed.wrapping_add(iteration)) - 0.5,
+									None => *shared.entry((t1.min(t2), t1.max(t2))).or_insert_with(|| random.next_u32() as f32 / u32::MAX as f32 - 0.5),
+								};
+								mid.pos[1] += offset * disp;
 
 								verts_out.push(mid);
 							}
@@ -563,17 +1091,1796 @@ impl MeshOperation {
 				inds_out = inds_in;
 			}
 
-			_ => {
+			Self::Biomes(moisture_amount, moisture_scale, rules) => {
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+
+				use noise::{NoiseFn, Simplex};
+				let noise = Simplex::new(random.next_u32());
+
+				for v in verts_out.iter_mut() {
+					// sampled in 3D, the 2D simplex noise has seams
+					let moisture = noise.get([(v.pos[0] / moisture_scale) as f64, (v.pos[2] / moisture_scale) as f64, 0.0]) as f32 * 0.5 + 0.5;
+					let moisture = (v.get(Attribute::Moisture) + moisture * moisture_amount).clamp(0.0, 1.0);
+					v.set(Attribute::Moisture, moisture);
+
+					let normal = Vector4::from(v.normal).truncate();
+					let slope = 1.0 - (normal.y / normal.magnitude()).abs();
+
+					v.set(Attribute::Biome, -1.0);
+					if let Some((i, rule)) = rules.iter().enumerate().find(|(_, r)| r.matches(v.pos[1], slope, moisture, v.get(Attribute::Insolation))) {
+						v.set(Attribute::Biome, i as f32);
+						v.color = rule.color;
+						for m in [Attribute::Grass, Attribute::Rock, Attribute::Sand, Attribute::Snow] {
+							v.set(m, if m == rule.material { 1.0 } else { 0.0 });
+						}
+					}
+				}
+			}
+
+			Self::Strata(base, tilt, fold, fold_scale, strata) => {
 				verts_out = verts_in;
 				inds_out = inds_in;
-				println!("todo");
+
+				let strata = Strata { strata: strata.clone(), base: *base, tilt: *tilt, fold: *fold, fold_scale: *fold_scale, seed: random.next_u32() };
+				strata.paint(&mut verts_out);
+				layers.strata = Some(strata);
 			}
-		}
 
+			Self::Accumulate(target, height, max_slope, direction, wind, shelter, depth) => {
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+				let neighbors = adjacency(&inds_out, verts_out.len());
+				let wind_dir = [direction.to_radians().cos(), direction.to_radians().sin()];
+
+				let coverage: Vec<f32> = verts_out.iter().enumerate().map(|(i, v)| {
+					let band = if height[0] == height[1] {
+						if v.pos[1] >= height[1] { 1.0 } else { 0.0 }
+					}
+					else {
+						((v.pos[1] - height[0]) / (height[1] - height[0])).clamp(0.0, 1.0)
+					};
+
+					let normal = Vector4::from(v.normal).truncate();
+					let normal = if normal.magnitude2() > 0.0 { normal.normalize() } else { normal };
+					let slope = 1.0 - normal.y.abs();
+					let flat = if *max_slope > 0.0 { ((max_slope - slope) / (max_slope * 0.25)).clamp(0.0, 1.0) } else { 0.0 };
 
+					// the normals point down, so the side facing the wind has its flipped normal against the wind
+					// and gets blown clean, while the lee side collects more
+					let lee = 1.0 + wind * (-normal.x * wind_dir[0] - normal.z * wind_dir[1]);
 
-		(verts_out, inds_out)
-	}
+					// hollows lying below their neighbors are sheltered, exposed bumps are not
+					let hollow = if neighbors[i].is_empty() {
+						0.0
+					}
+					else {
+						let (mut rise, mut run) = (0.0, 0.0);
+						for n in &neighbors[i] {
+							let o = verts_out[*n as usize].pos;
+							rise += o[1] - v.pos[1];
+							run += ((o[0] - v.pos[0]).powi(2) + (o[2] - v.pos[2]).powi(2)).sqrt();
+						}
+						rise / run.max(0.0001)
+					};
+					let sheltered = 1.0 + shelter * hollow;
+
+					(band * flat * lee.max(0.0) * sheltered.max(0.0)).clamp(0.0, 1.0)
+				}).collect();
+
+				for (v, c) in verts_out.iter_mut().zip(coverage) {
+					v.set(*target, c);
+					v.pos[1] += c * depth;
+
+					// snow covers the other materials and the color
+					if *target == Attribute::Snow {
+						for m in [Attribute::Grass, Attribute::Rock, Attribute::Sand] {
+							v.set(m, v.get(m) * (1.0 - c));
+						}
+						v.color[3] *= 1.0 - c;
+					}
+				}
+			}
+
+			Self::Scatter(name, spacing, max_slope, height, scale) => {
+				// poisson-disk sampling by dart throwing: random points on the surface are rejected
+				// if they are closer than `spacing` to an already accepted one
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+
+				let pos = |i: u32| Vector4::from(verts_out[i as usize].pos).truncate();
+				let normal = |i: u32| Vector4::from(verts_out[i as usize].normal).truncate();
+
+				let mut areas = Vec::with_capacity(inds_out.len() / 3);
+				let mut total = 0.0;
+				for t in inds_out.chunks_exact(3) {
+					total += (pos(t[1]) - pos(t[0])).cross(pos(t[2]) - pos(t[0])).magnitude() * 0.5;
+					areas.push(total);
+				}
+
+				let mut set = InstanceSet { name: name.clone(), instances: Vec::new() };
+				let mut grid = HashMap::<(i32, i32, i32), Vec<usize>>::new();
+				let cell = |p: cgmath::Vector3<f32>| ((p.x / spacing).floor() as i32, (p.y / spacing).floor() as i32, (p.z / spacing).floor() as i32);
+
+				let attempts = if total > 0.0 { (total / (spacing * spacing) * 4.0) as usize } else { 0 };
+				for _ in 0..attempts {
+					let target = unit(random) * total;
+					let t = areas.partition_point(|a| *a < target).min(areas.len() - 1);
+					let (i0, i1, i2) = (inds_out[3*t], inds_out[3*t+1], inds_out[3*t+2]);
+
+					let (mut u, mut v) = (unit(random), unit(random));
+					if u + v > 1.0 {
+						(u, v) = (1.0 - u, 1.0 - v);
+					}
+					let p = pos(i0) + (pos(i1) - pos(i0)) * u + (pos(i2) - pos(i0)) * v;
+					let mut n = (normal(i0) * (1.0 - u - v) + normal(i1) * u + normal(i2) * v).normalize();
+					if n.y < 0.0 {
+						n = -n;
+					}
+
+					if 1.0 - n.y > *max_slope || p.y < height[0] || p.y > height[1] {
+						continue;
+					}
+
+					let c = cell(p);
+					let mut free = true;
+					'search: for x in -1..=1 {
+						for y in -1..=1 {
+							for z in -1..=1 {
+								for other in grid.get(&(c.0 + x, c.1 + y, c.2 + z)).into_iter().flatten() {
+									if (cgmath::Vector3::from(set.instances[*other].pos) - p).magnitude() < *spacing {
+										free = false;
+										break 'search;
+									}
+								}
+							}
+						}
+					}
+					if !free {
+						continue;
+					}
+
+					grid.entry(c).or_default().push(set.instances.len());
+					set.instances.push(Instance {
+						pos: p.into(),
+						normal: n.into(),
+						rotation: unit(random) * std::f32::consts::TAU,
+						scale: scale[0] + unit(random) * (scale[1] - scale[0]),
+					});
+				}
+
+				layers.instance_sets.push(set);
+			}
+
+			Self::CarveRoad(points, width, shoulder, smoothing) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let path = catmull_rom(points, width * 0.25);
+				let points = carve_road(&mut verts_out, &path, *width, *shoulder, *smoothing);
+				layers.polylines.push(Polyline { name: "road".to_string(), points });
+			}
+
+			Self::RoadNetwork(sites, spacing, slope_penalty, water_level, width, shoulder) => {
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+				let neighbors = adjacency(&inds_out, verts_out.len());
+
+				let slope = |v: &Vertex| 1.0 - (v.normal[1] / Vector4::from(v.normal).truncate().magnitude()).abs();
+
+				// settlements are placed on the flattest of randomly picked dry vertices, keeping them apart
+				let mut placed = Vec::<usize>::new();
+				if !verts_out.is_empty() {
+					for _ in 0..*sites {
+						let mut best: Option<usize> = None;
+						for _ in 0..30 {
+							let i = random.next_u32() as usize % verts_out.len();
+							let v = &verts_out[i];
+							let far = placed.iter().all(|p| {
+								let o = &verts_out[*p];
+								(o.pos[0] - v.pos[0]).powi(2) + (o.pos[2] - v.pos[2]).powi(2) >= spacing * spacing
+							});
+							let flatter = match best {
+								Some(b) => slope(v) < slope(&verts_out[b]),
+								None => true,
+							};
+							if v.pos[1] > *water_level && far && flatter {
+								best = Some(i);
+							}
+						}
+						if let Some(b) = best {
+							placed.push(b);
+						}
+					}
+				}
+
+				// the sites are connected along a minimum spanning tree of their distances
+				let dist2 = |a: usize, b: usize| (verts_out[a].pos[0] - verts_out[b].pos[0]).powi(2) + (verts_out[a].pos[2] - verts_out[b].pos[2]).powi(2);
+				let mut connected = vec![false; placed.len()];
+				let mut edges = Vec::new();
+				if !placed.is_empty() {
+					connected[0] = true;
+				}
+				for _ in 1..placed.len() {
+					let mut best = (f32::MAX, 0, 0);
+					for a in (0..placed.len()).filter(|a| connected[*a]) {
+						for b in (0..placed.len()).filter(|b| !connected[*b]) {
+							let d = dist2(placed[a], placed[b]);
+							if d < best.0 {
+								best = (d, a, b);
+							}
+						}
+					}
+					connected[best.2] = true;
+					edges.push((placed[best.1], placed[best.2]));
+				}
+
+				let cost = |a: usize, b: usize| {
+					let (va, vb) = (&verts_out[a], &verts_out[b]);
+					let horizontal = dist2(a, b).sqrt();
+					let rise = (vb.pos[1] - va.pos[1]).abs();
+					let length = (horizontal * horizontal + rise * rise).sqrt();
+					let water = if vb.pos[1] <= *water_level { 100.0 } else { 1.0 };
+
+					length * (1.0 + slope_penalty * rise / horizontal.max(0.0001)) * water
+				};
+				let paths: Vec<Vec<[f32; 2]>> = edges.iter().filter_map(|(a, b)| {
+					let path = a_star(&verts_out, &neighbors, *a, *b, cost)?;
+					let mut path: Vec<[f32; 2]> = path.iter().map(|i| [verts_out[*i].pos[0], verts_out[*i].pos[2]]).collect();
+
+					// straighten the zigzag along the mesh edges
+					for _ in 0..4 {
+						path = (0..path.len()).map(|i| {
+							if i == 0 || i + 1 == path.len() {
+								path[i]
+							}
+							else {
+								[(path[i - 1][0] + path[i][0] + path[i + 1][0]) / 3.0, (path[i - 1][1] + path[i][1] + path[i + 1][1]) / 3.0]
+							}
+						}).collect();
+					}
+					Some(path)
+				}).collect();
+
+				for path in paths {
+					let points = carve_road(&mut verts_out, &path, *width, *shoulder, 20);
+					layers.polylines.push(Polyline { name: "road".to_string(), points });
+				}
+
+				layers.instance_sets.push(InstanceSet {
+					name: "settlements".to_string(),
+					instances: placed.iter().map(|i| Instance { pos: [verts_out[*i].pos[0], verts_out[*i].pos[1], verts_out[*i].pos[2]], normal: [0.0, 1.0, 0.0], rotation: 0.0, scale: 1.0 }).collect(),
+				});
+			}
+
+			Self::Plateau(cell, coverage, radius, sharpness, height) => {
+				// every cell of a worley grid has a feature point, cells chosen by `coverage` raise a mesa around it.
+				// Its top is flattened to the average height under it, raised by a random amount from the height range
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				use noise::{NoiseFn, Simplex};
+				let seed = random.next_u32();
+				let edge_noise = Simplex::new(random.next_u32());
+
+				let feature = |c: (i32, i32)| {
+					let h = cell_hash(c.0, c.1, seed);
+					[
+						(c.0 as f32 + (h & 0xffff) as f32 / 65535.0) * cell,
+						(c.1 as f32 + (h >> 16) as f32 / 65535.0) * cell,
+					]
+				};
+				let active = |c: (i32, i32)| (cell_hash(c.1, c.0, seed ^ 0x9e37_79b9) as f32 / u32::MAX as f32) < *coverage;
+
+				// the wall spans from the mesa's edge outwards, the sharper it is, the narrower it gets
+				let wall = (1.0 - sharpness).max(0.01);
+				// how many cells away a mesa can reach, with the widest wobble of its edge
+				let span = ((1.0 + wall) * radius * 0.5 * 1.3).ceil().max(1.0) as i32;
+
+				// the feature points in the cells around each vertex and the distances to them, relative to the mesa's radius,
+				// the nearest first
+				let regions: Vec<Vec<((i32, i32), f32)>> = verts_out.iter().map(|v| {
+					let (x, z) = (v.pos[0], v.pos[2]);
+					let home = ((x / cell).floor() as i32, (z / cell).floor() as i32);
+					// sampled in 3D, the 2D simplex noise has seams
+					let wobble = 1.0 + 0.3 * edge_noise.get([(x / cell * 2.0) as f64, (z / cell * 2.0) as f64, 0.0]) as f32;
+					let mut around: Vec<((i32, i32), f32)> = (-span..=span).flat_map(|dx| (-span..=span).map(move |dz| (home.0 + dx, home.1 + dz))).map(|c| {
+						let f = feature(c);
+						let d = ((x - f[0]).powi(2) + (z - f[1]).powi(2)).sqrt();
+						(c, d / (radius * cell * 0.5 * wobble).max(0.0001))
+					}).collect();
+					around.sort_by(|a, b| a.1.total_cmp(&b.1));
+					around
+				}).collect();
+
+				// a mesa's top is the average height of the vertices in its own cell
+				let mut tops = HashMap::<(i32, i32), (f32, f32)>::new();
+				for (v, (c, d)) in verts_out.iter().zip(regions.iter().map(|around| around[0])) {
+					if d < 1.0 && active(c) {
+						let top = tops.entry(c).or_insert((0.0, 0.0));
+						top.0 += v.pos[1];
+						top.1 += 1.0;
+					}
+				}
+				let tops: HashMap<(i32, i32), f32> = tops.into_iter().map(|(c, (sum, count))| {
+					let raise = height[0] + (cell_hash(c.0 ^ 0x5bd1, c.1, seed) as f32 / u32::MAX as f32) * (height[1] - height[0]);
+					(c, sum / count + raise)
+				}).collect();
+
+				// the wall can reach into the neighboring cells, so every mesa reaching a vertex blends it,
+				// where they overlap the highest wins
+				for (v, around) in verts_out.iter_mut().zip(regions.iter()) {
+					let blended = around.iter().filter_map(|(c, d)| {
+						let top = tops.get(c)?;
+						let t = ((1.0 + wall - d) / wall).clamp(0.0, 1.0);
+						if t <= 0.0 {
+							return None;
+						}
+						let t = t * t * (3.0 - 2.0 * t);
+						Some(v.pos[1] * (1.0 - t) + top * t)
+					}).reduce(f32::max);
+					if let Some(y) = blended {
+						v.pos[1] = y;
+					}
+				}
+			}
+
+			Self::FaultFormation(iterations, displacement_start, displacement_end, falloff) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let mut min = [f32::MAX; 2];
+				let mut max = [f32::MIN; 2];
+				for v in verts_out.iter() {
+					min = [min[0].min(v.pos[0]), min[1].min(v.pos[2])];
+					max = [max[0].max(v.pos[0]), max[1].max(v.pos[2])];
+				}
+
+				for iteration in 0..*iterations {
+					// the line goes through a random point of the footprint in a random direction
+					let point = [min[0] + unit(random) * (max[0] - min[0]), min[1] + unit(random) * (max[1] - min[1])];
+					let angle = unit(random) * std::f32::consts::TAU;
+					let normal = [angle.cos(), angle.sin()];
+
+					let t = if *iterations > 1 { iteration as f32 / (*iterations - 1) as f32 } else { 0.0 };
+					let disp = displacement_start + (displacement_end - displacement_start) * t;
+
+					for v in verts_out.iter_mut() {
+						let side = (v.pos[0] - point[0]) * normal[0] + (v.pos[2] - point[1]) * normal[1];
+						let step =
+							if *falloff > 0.0 {
+								let t = (side / falloff * 0.5 + 0.5).clamp(0.0, 1.0);
+								t * t * (3.0 - 2.0 * t) * 2.0 - 1.0
+							}
+							else {
+								side.signum()
+							};
+						v.pos[1] += step * disp * 0.5;
+					}
+				}
+			}
+
+			Self::ParticleDeposition(particles, emitters, drift, height, repose) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				if verts_out.is_empty() {
+					return (verts_out, inds_out);
+				}
+				let neighbors = adjacency(&inds_out, verts_out.len());
+
+				let mut emitter_positions: Vec<usize> = (0..*emitters).map(|_| random.next_u32() as usize % verts_out.len()).collect();
+
+				for p in 0..*particles {
+					let e = p as usize % emitter_positions.len();
+
+					// the emitter wanders to a random neighbor from time to time
+					if unit(random) < *drift && !neighbors[emitter_positions[e]].is_empty() {
+						let n = &neighbors[emitter_positions[e]];
+						emitter_positions[e] = n[random.next_u32() as usize % n.len()] as usize;
+					}
+
+					// the particle rolls down the steepest slope, until no slope is steeper than the repose slope
+					let mut i = emitter_positions[e];
+					loop {
+						let v = verts_out[i].pos;
+						let steepest = neighbors[i].iter().map(|n| {
+							let o = verts_out[*n as usize].pos;
+							let run = ((o[0] - v[0]).powi(2) + (o[2] - v[2]).powi(2)).sqrt().max(0.0001);
+							(*n as usize, (v[1] - o[1]) / run)
+						}).max_by(|a, b| a.1.total_cmp(&b.1));
+
+						match steepest {
+							Some((n, slope)) if slope > *repose => i = n,
+							_ => break,
+						}
+					}
+
+					verts_out[i].pos[1] += height;
+				}
+			}
+
+			Self::WindErosion(direction, strength, iterations, hop, repose) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				if verts_out.is_empty() {
+					return (verts_out, inds_out);
+				}
+				// when tiling, only the canonical twins take part, wrapping across the opposite edges
+				let tiles = Tiles::new(&verts_out, layers.tiling);
+				let neighbors = tiles.adjacency(&inds_out);
+				let wind = [direction.to_radians().cos(), direction.to_radians().sin()];
+
+				// the neighbor lying the most in the given direction, none if no neighbor is close enough to it (on the mesh's edge)
+				let follow = |dir: [f32; 2]| -> Vec<Option<usize>> {
+					neighbors.iter().enumerate().map(|(i, n)| {
+						let v = verts_out[i].pos;
+						n.iter().map(|n| {
+							let o = verts_out[*n as usize].pos;
+							let (dx, dz) = (tiles.wrap(o[0] - v[0]), tiles.wrap(o[2] - v[2]));
+							(*n as usize, (dx * dir[0] + dz * dir[1]) / (dx * dx + dz * dz).sqrt().max(0.0001))
+						}).filter(|(_, cos)| *cos > 0.5).max_by(|a, b| a.1.total_cmp(&b.1)).map(|(n, _)| n)
+					}).collect()
+				};
+				let downwind = follow(wind);
+				let upwind = follow([-wind[0], -wind[1]]);
+
+				let run = |verts: &[Vertex], a: usize, b: usize| {
+					let (pa, pb) = (verts[a].pos, verts[b].pos);
+					(tiles.wrap(pa[0] - pb[0]).powi(2) + tiles.wrap(pa[2] - pb[2]).powi(2)).sqrt().max(0.0001)
+				};
+
+				// in the wind shadow of something upwind rising above 15 degrees
+				let shadow_reach = *hop as usize * 3;
+				let shadowed = |verts: &[Vertex], i: usize| {
+					let (mut j, mut distance) = (i, 0.0);
+					for _ in 0..shadow_reach {
+						let Some(u) = upwind[j] else { return false };
+						distance += run(verts, j, u);
+						j = u;
+						if (verts[j].pos[1] - verts[i].pos[1]) / distance > 0.27 {
+							return true;
+						}
+					}
+					false
+				};
+
+				let strata_noise = layers.strata.as_ref().map(|s| s.noise());
+
+				for _ in 0..*iterations {
+					for _ in 0..verts_out.len() {
+						let i = tiles.twins[random.next_u32() as usize % verts_out.len()];
+						if shadowed(&verts_out, i) {
+							continue;
+						}
+
+						// slopes facing the wind and ridges are worn down the most, hard rock layers the least, sand is always loose
+						let windward = upwind[i].map_or(0.0, |u| (verts_out[i].pos[1] - verts_out[u].pos[1]) / run(&verts_out, i, u));
+						let hardness = match (&layers.strata, &strata_noise) {
+							(Some(strata), Some(noise)) if verts_out[i].get(Attribute::Sediment) <= 0.0 => strata.hardness_at(noise, verts_out[i].pos),
+							_ => 0.0,
+						};
+						let slab = strength * (1.0 + windward.clamp(0.0, 1.0)) * (1.0 - hardness);
+						if slab <= 0.0 {
+							continue;
+						}
+						verts_out[i].pos[1] -= slab;
+						let sand = verts_out[i].get(Attribute::Sediment);
+						verts_out[i].set(Attribute::Sediment, (sand - slab).max(0.0));
+
+						// the slab hops downwind until it lands in a shadow or on sand, material blown off the mesh is lost
+						let mut j = i;
+						let landed = 'hops: loop {
+							for _ in 0..*hop {
+								match downwind[j] {
+									Some(d) => j = d,
+									None => break 'hops None,
+								}
+							}
+							let chance = if verts_out[j].get(Attribute::Sediment) > 0.0 { 0.6 } else { 0.4 };
+							if shadowed(&verts_out, j) || unit(random) < chance {
+								break Some(j);
+							}
+						};
+						let Some(mut j) = landed else { continue };
+
+						// and slides down until no slope is steeper than the repose slope, forming the slip face
+						loop {
+							let steepest = neighbors[j].iter().map(|n| *n as usize).filter(|n| verts_out[*n].pos[1] < verts_out[j].pos[1]).map(|n| {
+								(n, (verts_out[j].pos[1] + slab - verts_out[n].pos[1]) / run(&verts_out, j, n))
+							}).max_by(|a, b| a.1.total_cmp(&b.1));
+
+							match steepest {
+								Some((n, slope)) if slope > *repose => j = n,
+								_ => break,
+							}
+						}
+
+						verts_out[j].pos[1] += slab;
+						let sand = verts_out[j].get(Attribute::Sediment);
+						verts_out[j].set(Attribute::Sediment, sand + slab);
+					}
+				}
+
+				if let Some(strata) = &layers.strata {
+					strata.paint(&mut verts_out);
+				}
+			}
+
+			Self::GlacialCarve(ela, strength, width) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				if verts_out.is_empty() || *width <= 0.0 {
+					return (verts_out, inds_out);
+				}
+				// when tiling, the ice flows through the canonical twins, across the opposite edges
+				let tiles = Tiles::new(&verts_out, layers.tiling);
+				let neighbors = tiles.adjacency(&inds_out);
+
+				// ice accumulates above the equilibrium line and melts below it, flowing down the steepest descent
+				let mut order: Vec<usize> = (0..verts_out.len()).collect();
+				order.sort_by(|a, b| verts_out[*b].pos[1].total_cmp(&verts_out[*a].pos[1]));
+				let mut ice = vec![0.0f32; verts_out.len()];
+				for i in order.into_iter().filter(|i| tiles.twins[*i] == *i) {
+					let h = verts_out[i].pos[1];
+					ice[i] = (ice[i] + h - ela).max(0.0);
+					let lowest = neighbors[i].iter().map(|n| *n as usize).min_by(|a, b| verts_out[*a].pos[1].total_cmp(&verts_out[*b].pos[1]));
+					if let Some(n) = lowest.filter(|n| verts_out[*n].pos[1] < h) {
+						ice[n] += ice[i];
+					}
+				}
+
+				for i in 0..ice.len() {
+					ice[i] = ice[tiles.twins[i]];
+				}
+
+				let most = ice.iter().copied().fold(0.0, f32::max);
+				if most <= 0.0 {
+					return (verts_out, inds_out);
+				}
+
+				// the glacier's flow lines, thicker glaciers carve deeper and wider valleys
+				let mut cells = HashMap::<(i32, i32), Vec<(usize, f32, f32)>>::new();
+				for (i, q) in ice.iter().enumerate() {
+					let thickness = (q / most).sqrt();
+					if thickness > 0.1 {
+						let p = verts_out[i].pos;
+						cells.entry(((p[0] / width).floor() as i32, (p[2] / width).floor() as i32)).or_default()
+							.push((i, strength * thickness, width * thickness));
+					}
+				}
+
+				// the floor is flat around the flow line, the walls rise steeply towards the valley's edge
+				let original: Vec<[f32; 4]> = verts_out.iter().map(|v| v.pos).collect();
+				let strata_noise = layers.strata.as_ref().map(|s| s.noise());
+				// when tiling, the flow lines are looked for in the neighboring copies of the tile too
+				let images = match tiles.period {
+					Some(period) => vec![-period, 0.0, period],
+					None => vec![0.0],
+				};
+				for (v, p) in verts_out.iter_mut().zip(&original) {
+					for (ix, iz) in images.iter().flat_map(|ix| images.iter().map(move |iz| (*ix, *iz))) {
+						let (px, pz) = (p[0] + ix, p[2] + iz);
+						let (cx, cz) = ((px / width).floor() as i32, (pz / width).floor() as i32);
+						for dx in -1..=1 {
+							for dz in -1..=1 {
+								let Some(cell) = cells.get(&(cx + dx, cz + dz)) else { continue };
+								for (c, depth, half_width) in cell {
+									let o = original[*c];
+									let d = ((o[0] - px).powi(2) + (o[2] - pz).powi(2)).sqrt() / half_width;
+									if d < 1.0 {
+										let floor = o[1] - depth;
+										v.pos[1] = v.pos[1].min(floor + (p[1] - floor) * d.powi(4));
+									}
+								}
+							}
+						}
+					}
+
+					// harder rock layers give way less
+					if let (Some(strata), Some(noise)) = (&layers.strata, &strata_noise) {
+						v.pos[1] = p[1] - (p[1] - v.pos[1]) * (1.0 - strata.hardness_at(noise, *p));
+					}
+				}
+
+				if let Some(strata) = &layers.strata {
+					strata.paint(&mut verts_out);
+				}
+			}
+
+			Self::ImproveTriangles(passes, delaunay, ridges) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let length = |a: u32, b: u32| (Vector4::from(verts_out[a as usize].pos) - Vector4::from(verts_out[b as usize].pos)).magnitude();
+
+				for _ in 0..*passes {
+					// how much a vertex sticks out of its neighbors, positive on ridges, negative in valleys
+					let curvature: Vec<f32> = adjacency(&inds_out, verts_out.len()).iter().enumerate().map(|(i, n)| {
+						if n.is_empty() {
+							return 0.0;
+						}
+						verts_out[i].pos[1] - n.iter().map(|n| verts_out[*n as usize].pos[1]).sum::<f32>() / n.len() as f32
+					}).collect();
+					let feature = |a: u32, b: u32| (curvature[a as usize] + curvature[b as usize]).abs();
+
+					let flips = flip_edges(&verts_out, &mut inds_out, |a, b, c, d| {
+						// a diagonal following a ridge or a valley wins, if it follows it better enough
+						if *ridges > 0.0 && (feature(c, d) - feature(a, b)).abs() > *ridges {
+							return feature(c, d) > feature(a, b);
+						}
+
+						if *delaunay {
+							// d inside the circumcircle of a, b, c
+							let xz = |i: u32| [verts_out[i as usize].pos[0], verts_out[i as usize].pos[2]];
+							let (pa, pb, pc, pd) = (xz(a), xz(b), xz(c), xz(d));
+							let row = |p: [f32; 2]| [p[0] - pd[0], p[1] - pd[1], (p[0] - pd[0]).powi(2) + (p[1] - pd[1]).powi(2)];
+							let (ra, rb, rc) = (row(pa), row(pb), row(pc));
+							let det =
+								ra[0] * (rb[1] * rc[2] - rb[2] * rc[1]) -
+								ra[1] * (rb[0] * rc[2] - rb[2] * rc[0]) +
+								ra[2] * (rb[0] * rc[1] - rb[1] * rc[0]);
+							det * orient_xz(&verts_out, a, b, c).signum() > 0.0
+						}
+						else {
+							length(c, d) < length(a, b)
+						}
+					});
+					if flips == 0 {
+						break;
+					}
+				}
+			}
+
+			Self::Remesh(length, iterations) => {
+				// isotropic remeshing: long edges are split, short ones collapsed, edges are flipped towards
+				// the regular valence and the vertices are relaxed along the surface, then put back on the original one
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let surface = TriangleGrid::new(&verts_out, &inds_out);
+				let (low, high) = (length * 4.0 / 5.0, length * 4.0 / 3.0);
+
+				for _ in 0..*iterations {
+					for _ in 0..10 {
+						let count = inds_out.len();
+						inds_out = split_long_edges(&mut verts_out, &inds_out, high);
+						if inds_out.len() == count {
+							break;
+						}
+					}
+
+					for _ in 0..10 {
+						let count = inds_out.len();
+						(verts_out, inds_out) = collapse_short_edges(verts_out, inds_out, low, high);
+						if inds_out.len() == count {
+							break;
+						}
+					}
+
+					let boundary = boundary_vertices(&inds_out, verts_out.len());
+					let target = |i: u32| if boundary[i as usize] { 4 } else { 6 };
+					let mut valence: Vec<i32> = adjacency(&inds_out, verts_out.len()).iter().map(|n| n.len() as i32).collect();
+					flip_edges(&verts_out, &mut inds_out, |a, b, c, d| {
+						let deviation = |change: [i32; 4]| [a, b, c, d].iter().zip(change).map(|(i, change)| (valence[*i as usize] + change - target(*i)).abs()).sum::<i32>();
+						if deviation([-1, -1, 1, 1]) < deviation([0; 4]) {
+							valence[a as usize] -= 1;
+							valence[b as usize] -= 1;
+							valence[c as usize] += 1;
+							valence[d as usize] += 1;
+							return true;
+						}
+						false
+					});
+
+					let (normals, _) = calculate_normals(verts_out.clone(), inds_out.clone());
+					let neighbors = adjacency(&inds_out, verts_out.len());
+					let relaxed: Vec<Vertex> = verts_out.iter().enumerate().map(|(i, v)| {
+						if boundary[i] || neighbors[i].is_empty() {
+							return *v;
+						}
+						let pos = Vector4::from(v.pos).truncate();
+						let centroid = neighbors[i].iter().map(|n| Vector4::from(verts_out[*n as usize].pos).truncate()).sum::<cgmath::Vector3<f32>>() / neighbors[i].len() as f32;
+						let normal = Vector4::from(normals[i].normal).truncate();
+						let mut disp = centroid - pos;
+						if normal.magnitude2() > 0.0 {
+							let normal = normal.normalize();
+							disp -= normal * disp.dot(normal);
+						}
+						let pos = pos + disp * 0.5;
+
+						match surface.sample(pos.x, pos.z) {
+							Some(on_surface) => Vertex { pos: [pos.x, on_surface.pos[1], pos.z, 1.0], normal: v.normal, ..on_surface },
+							None => *v,
+						}
+					}).collect();
+					verts_out = relaxed;
+				}
+			}
+
+			Self::SpectralSynthesis(amount, beta, resolution, anisotropy, angle) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let n = resolution.next_power_of_two() as usize;
+				let (sin, cos) = angle.to_radians().sin_cos();
+
+				// random phases, the amplitude falls off as 1/f^beta. The frequencies along the anisotropy angle
+				// are scaled by the anisotropy, so that the features stretch across it
+				let mut grid = vec![(0.0f32, 0.0f32); n * n];
+				for z in 0..n {
+					for x in 0..n {
+						let kx = if x < n / 2 { x as f32 } else { x as f32 - n as f32 };
+						let kz = if z < n / 2 { z as f32 } else { z as f32 - n as f32 };
+						let along = (kx * cos + kz * sin) * anisotropy;
+						let across = -kx * sin + kz * cos;
+						let f = (along * along + across * across).sqrt();
+						let phase = unit(random) * std::f32::consts::TAU;
+						if f > 0.0 {
+							let a = f.powf(-beta);
+							grid[z * n + x] = (a * phase.cos(), a * phase.sin());
+						}
+					}
+				}
+
+				for row in grid.chunks_exact_mut(n) {
+					fft(row, true);
+				}
+				let mut column = vec![(0.0, 0.0); n];
+				for x in 0..n {
+					for z in 0..n {
+						column[z] = grid[z * n + x];
+					}
+					fft(&mut column, true);
+					for z in 0..n {
+						grid[z * n + x] = column[z];
+					}
+				}
+
+				let max = grid.iter().fold(0.0f32, |m, c| m.max(c.0.abs())).max(0.0001);
+				let heights: Vec<f32> = grid.iter().map(|c| c.0 / max).collect();
+
+				// the grid covers the square around the mesh, it repeats, so the samples wrap around
+				let (corner, extent) = footprint(&verts_out);
+				for v in verts_out.iter_mut() {
+					let x = (v.pos[0] - corner[0]) / extent * n as f32;
+					let z = (v.pos[2] - corner[1]) / extent * n as f32;
+					v.pos[1] += sample_grid(&heights, n, x, z, true) * amount;
+				}
+			}
+
+			Self::DlaRidges(amount, resolution, density, passes) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let n = (*resolution as usize).max(2);
+				let mut parent = vec![usize::MAX; n * n];
+				let mut order = Vec::<usize>::new();
+
+				// the tree grows from the center, walkers start around it and stick where they first touch it
+				let root = n / 2 * n + n / 2;
+				parent[root] = root;
+				order.push(root);
+				let (mut low, mut high) = ([n / 2; 2], [n / 2; 2]);
+
+				let particles = (density * (n * n) as f32) as usize;
+				let mut steps = 0usize;
+				while order.len() < particles && steps < particles * n * n {
+					// walkers live in the tree's bounding box with a margin, which speeds up the growth a lot
+					let lo = [low[0].saturating_sub(3), low[1].saturating_sub(3)];
+					let hi = [(high[0] + 3).min(n - 1), (high[1] + 3).min(n - 1)];
+					let mut x = lo[0] + random.next_u32() as usize % (hi[0] - lo[0] + 1);
+					let mut z = lo[1] + random.next_u32() as usize % (hi[1] - lo[1] + 1);
+					steps += 1;
+					if parent[z * n + x] != usize::MAX {
+						continue;
+					}
+
+					loop {
+						steps += 1;
+						let touching = [(1, 0), (-1, 0), (0, 1), (0, -1)].iter().find_map(|(dx, dz): &(i64, i64)| {
+							let (nx, nz) = (x as i64 + dx, z as i64 + dz);
+							if nx < 0 || nz < 0 || nx >= n as i64 || nz >= n as i64 {
+								return None;
+							}
+							let i = nz as usize * n + nx as usize;
+							if parent[i] != usize::MAX { Some(i) } else { None }
+						});
+						if let Some(p) = touching {
+							parent[z * n + x] = p;
+							order.push(z * n + x);
+							low = [low[0].min(x), low[1].min(z)];
+							high = [high[0].max(x), high[1].max(z)];
+							break;
+						}
+
+						match random.next_u32() % 4 {
+							0 => x += 1,
+							1 => x = x.wrapping_sub(1),
+							2 => z += 1,
+							_ => z = z.wrapping_sub(1),
+						}
+						if x < lo[0] || x > hi[0] || z < lo[1] || z > hi[1] {
+							break;
+						}
+					}
+				}
+
+				// a cell is as high as the longest branch growing out of it, so the crests rise towards the root
+				let mut reach = vec![0.0f32; n * n];
+				for i in order.iter().rev() {
+					let p = parent[*i];
+					if p != *i {
+						reach[p] = reach[p].max(reach[*i] + 1.0);
+					}
+				}
+				let mut heights: Vec<f32> = (0..n * n).map(|i| if parent[i] != usize::MAX { 1.0 - 1.0 / (1.0 + reach[i] / 4.0) } else { 0.0 }).collect();
+
+				// every blur pass widens the ridges, summing them keeps the sharp crests on broad slopes
+				let mut sum = heights.clone();
+				for _ in 0..*passes {
+					heights = blur_grid(&heights, n);
+					for (s, h) in sum.iter_mut().zip(heights.iter()) {
+						*s += h;
+					}
+				}
+				let max = sum.iter().fold(0.0f32, |m, h| m.max(*h)).max(0.0001);
+
+				let (corner, extent) = footprint(&verts_out);
+				for v in verts_out.iter_mut() {
+					let x = (v.pos[0] - corner[0]) / extent * (n - 1) as f32;
+					let z = (v.pos[2] - corner[1]) / extent * (n - 1) as f32;
+					v.pos[1] += sample_grid(&sum, n, x, z, false) / max * amount;
+				}
+			}
+
+			Self::CellularIslands(resolution, fill, rule, iterations, height) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let n = (*resolution as usize).max(2);
+				let (birth, survival) = parse_rule(rule);
+
+				let mut cells: Vec<bool> = (0..n * n).map(|_| unit(random) < *fill).collect();
+				for _ in 0..*iterations {
+					// cells outside of the grid are dead, so the land doesn't touch the edges
+					cells = (0..n * n).map(|i| {
+						let (x, z) = ((i % n) as i64, (i / n) as i64);
+						let mut alive = 0;
+						for dz in -1..=1 {
+							for dx in -1..=1 {
+								let (nx, nz) = (x + dx, z + dz);
+								if (dx, dz) != (0, 0) && nx >= 0 && nz >= 0 && nx < n as i64 && nz < n as i64 && cells[nz as usize * n + nx as usize] {
+									alive += 1;
+								}
+							}
+						}
+						if cells[i] { survival[alive] } else { birth[alive] }
+					}).collect();
+				}
+
+				let mut mask: Vec<f32> = cells.iter().map(|c| if *c { 1.0 } else { 0.0 }).collect();
+				for _ in 0..2 {
+					mask = blur_grid(&mask, n);
+				}
+
+				// the land keeps its shape and is raised, the sea is flattened and lowered
+				let (corner, extent) = footprint(&verts_out);
+				for v in verts_out.iter_mut() {
+					let x = (v.pos[0] - corner[0]) / extent * (n - 1) as f32;
+					let z = (v.pos[2] - corner[1]) / extent * (n - 1) as f32;
+					let m = sample_grid(&mask, n, x, z, false);
+					let m = m * m * (3.0 - 2.0 * m);
+					v.pos[1] = v.pos[1] * m + (2.0 * m - 1.0) * height;
+				}
+			}
+
+			Self::Skirt(depth) => {
+				// the skirt is added by `skirt` after the normals are calculated (and a chunk is cropped),
+				// so that it hangs from the final boundary and takes the normals of the surface above it
+				verts_out = verts_in;
+				inds_out = inds_in;
+				layers.skirt = Some(*depth);
+			},
+
+			Self::AmbientOcclusion(rays, distance) => {
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+				let bvh = Bvh::new(&verts_out, &inds_out);
+
+				for v in verts_out.iter_mut() {
+					// the normals point down, the rays go into the hemisphere above the surface
+					let normal = -Vector4::from(v.normal).truncate();
+					let up = if normal.magnitude2() > 0.0 { normal.normalize() } else { cgmath::Vector3::unit_y() };
+					let tangent = if up.x.abs() < 0.9 { up.cross(cgmath::Vector3::unit_x()) } else { up.cross(cgmath::Vector3::unit_z()) }.normalize();
+					let bitangent = up.cross(tangent);
+					let origin = Vector4::from(v.pos).truncate() + up * 0.0001;
+
+					// cosine weighted directions, so that the rays near the horizon count less
+					let mut hits = 0;
+					for _ in 0..*rays {
+						let (r, angle) = (unit(random).sqrt(), unit(random) * std::f32::consts::TAU);
+						let dir = tangent * r * angle.cos() + bitangent * r * angle.sin() + up * (1.0 - r * r).max(0.0).sqrt();
+						if bvh.occluded(origin, dir.normalize(), *distance) {
+							hits += 1;
+						}
+					}
+					v.set(Attribute::Occlusion, hits as f32 / (*rays).max(1) as f32);
+				}
+			}
+
+			Self::Insolation(latitude, yearly, day, samples, distance) => {
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+				let bvh = Bvh::new(&verts_out, &inds_out);
+
+				// directions to the sun above the horizon: X is east, Z is south, a day in the middle of every month for a whole year
+				let days: Vec<f32> = if *yearly { (0..12).map(|m| 15.0 + m as f32 * 365.0 / 12.0).collect() } else { vec![*day as f32] };
+				let phi = latitude.to_radians();
+				let mut suns = Vec::new();
+				let mut possible = 0.0;
+				for d in days {
+					let declination = 23.44f32.to_radians() * (std::f32::consts::TAU * (284.0 + d) / 365.0).sin();
+					for s in 0..*samples {
+						let hour = ((s as f32 + 0.5) / *samples as f32 - 0.5) * std::f32::consts::TAU;
+						let east = -declination.cos() * hour.sin();
+						let north = phi.cos() * declination.sin() - phi.sin() * declination.cos() * hour.cos();
+						let up = phi.sin() * declination.sin() + phi.cos() * declination.cos() * hour.cos();
+						if up > 0.0 {
+							suns.push(cgmath::Vector3::new(east, up, -north).normalize());
+							possible += 1.0;
+						}
+					}
+				}
+
+				for v in verts_out.iter_mut() {
+					// the normals point down
+					let normal = -Vector4::from(v.normal).truncate();
+					let up = if normal.magnitude2() > 0.0 { normal.normalize() } else { cgmath::Vector3::unit_y() };
+					let origin = Vector4::from(v.pos).truncate() + up * 0.0001;
+
+					let received: f32 = suns.iter().map(|sun| {
+						let cos = up.dot(*sun);
+						if cos <= 0.0 || bvh.occluded(origin, *sun, *distance) { 0.0 } else { cos }
+					}).sum();
+					v.set(Attribute::Insolation, if possible > 0.0 { received / possible } else { 0.0 });
+				}
+			}
+
+			Self::Viewshed(observer, height, range) => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+
+				let bvh = Bvh::new(&verts_out, &inds_out);
+				let ground = TriangleGrid::new(&verts_out, &inds_out).sample(observer[0], observer[1]).map_or(0.0, |v| v.pos[1]);
+				let eye = cgmath::Vector3::new(observer[0], ground + height, observer[1]);
+
+				for v in verts_out.iter_mut() {
+					// aiming a bit above the vertex, so that the triangles around it don't hide it
+					let target = Vector4::from(v.pos).truncate() + cgmath::Vector3::unit_y() * 0.001;
+					let distance = (target - eye).magnitude();
+					let visible = if distance <= 0.0001 {
+						true
+					}
+					else if *range > 0.0 && distance > *range {
+						false
+					}
+					else {
+						!bvh.occluded(eye, (target - eye) / distance, distance * 0.999)
+					};
+					v.set(Attribute::Visibility, if visible { 1.0 } else { 0.0 });
+				}
+			}
+
+			Self::Walkability(max_slope, clearance, spawn) => {
+				// the normals are calculated here as well, so that the walkable surface copied out has them
+				(verts_out, inds_out) = calculate_normals(verts_in, inds_in);
+
+				let bvh = Bvh::new(&verts_out, &inds_out);
+				let pos = |i: u32| Vector4::from(verts_out[i as usize].pos).truncate();
+
+				// flat enough, with nothing overhanging too close above
+				let triangles = inds_out.len() / 3;
+				let walkable: Vec<bool> = inds_out.chunks_exact(3).map(|t| {
+					let normal = (pos(t[1]) - pos(t[0])).cross(pos(t[2]) - pos(t[0]));
+					if normal.magnitude2() <= 0.0 || 1.0 - (normal.y / normal.magnitude()).abs() > *max_slope {
+						return false;
+					}
+					let centroid = (pos(t[0]) + pos(t[1]) + pos(t[2])) / 3.0;
+					!bvh.occluded(centroid + cgmath::Vector3::unit_y() * 0.0001, cgmath::Vector3::unit_y(), *clearance)
+				}).collect();
+
+				// walkable triangles sharing an edge belong to the same region
+				let mut region: Vec<usize> = (0..triangles).collect();
+				fn root(region: &mut [usize], mut t: usize) -> usize {
+					while region[t] != t {
+						region[t] = region[region[t]];
+						t = region[t];
+					}
+					t
+				}
+				let mut edges = HashMap::<(u32, u32), usize>::new();
+				for t in (0..triangles).filter(|t| walkable[*t]) {
+					for k in 0..3 {
+						let (a, b) = (inds_out[3*t + k], inds_out[3*t + (k + 1) % 3]);
+						match edges.entry((a.min(b), a.max(b))) {
+							std::collections::hash_map::Entry::Occupied(other) => {
+								let (ra, rb) = (root(&mut region, t), root(&mut region, *other.get()));
+								region[ra] = rb;
+							}
+							std::collections::hash_map::Entry::Vacant(entry) => { entry.insert(t); }
+						}
+					}
+				}
+
+				// the spawn is in the region of the closest walkable triangle
+				let centroid_xz = |t: usize| {
+					let c = (pos(inds_out[3*t]) + pos(inds_out[3*t + 1]) + pos(inds_out[3*t + 2])) / 3.0;
+					(c.x - spawn[0]).powi(2) + (c.z - spawn[1]).powi(2)
+				};
+				let spawn_region = (0..triangles).filter(|t| walkable[*t])
+					.min_by(|a, b| centroid_xz(*a).total_cmp(&centroid_xz(*b)))
+					.map(|t| root(&mut region, t));
+
+				let mut areas = HashMap::<usize, f32>::new();
+				let mut values = vec![0.0f32; verts_out.len()];
+				let mut surface_inds = Vec::new();
+				for t in (0..triangles).filter(|t| walkable[*t]) {
+					let r = root(&mut region, t);
+					let tri = [inds_out[3*t], inds_out[3*t + 1], inds_out[3*t + 2]];
+					*areas.entry(r).or_default() += (pos(tri[1]) - pos(tri[0])).cross(pos(tri[2]) - pos(tri[0])).magnitude() * 0.5;
+
+					let value = if Some(r) == spawn_region { 1.0 } else { 0.5 };
+					for i in tri {
+						values[i as usize] = values[i as usize].max(value);
+					}
+					surface_inds.extend(tri);
+				}
+				for (v, value) in verts_out.iter_mut().zip(values) {
+					v.set(Attribute::Walkable, value);
+				}
+
+				// the walkable surface on its own, with only the vertices it uses
+				layers.walkable = compact(&verts_out, surface_inds);
+
+				let total: f32 = areas.values().sum();
+				let reachable = spawn_region.and_then(|r| areas.get(&r)).copied().unwrap_or(0.0);
+				layers.reports.push(format!(
+					"Walkability: {} walkable regions, {} unreachable islands, {:.1}% of the walkable area reachable from the spawn",
+					areas.len(), areas.len().saturating_sub(1), if total > 0.0 { reachable / total * 100.0 } else { 0.0 }));
+			}
+
+			_ => {
+				verts_out = verts_in;
+				inds_out = inds_in;
+				println!("todo");
+			}
+		}
+
+
+
+		(verts_out, inds_out)
+	}
+}
+
+pub fn calculate_normals(verts: Vec<Vertex>, inds: Vec<u32>) -> (Vec<Vertex>, Vec<u32>) {
+	let mut verts: Vec<Vertex> = verts.iter().map(|v| Vertex {normal: [0.0, 0.0, 0.0, 0.0], ..*v}).collect();
+
+	for i in 0..(inds.len() / 3) {
+		let i = [
+			inds[3*i+0] as usize,
+			inds[3*i+1] as usize,
+			inds[3*i+2] as usize,
+		];
+		//    1
+		//  /   \
+		// 2-----0
+		const IDS: [[usize; 3]; 3] = [
+			[0, 1, 2],
+			[1, 2, 0],
+			[2, 0, 1],
+		];
+
+		for [id1, id2, id3] in IDS {
+			let (i1, i2, i3) = (i[id1], i[id2], i[id3]);
+
+			let disp1 = Vector4::from(verts[i2].pos).truncate() - Vector4::from(verts[i1].pos).truncate();
+			let disp2 = Vector4::from(verts[i3].pos).truncate() - Vector4::from(verts[i1].pos).truncate();
+			let normal: [f32; 4] = disp1.cross(disp2).normalize().extend(1.0).into();
+			verts[i1].normal[0] += normal[0];
+			verts[i1].normal[1] += normal[1];
+			verts[i1].normal[2] += normal[2];
+			verts[i1].normal[3] += normal[3];
+		}
+	}
+
+	(verts.iter_mut().map(|v| {
+		v.normal[0] /= v.normal[3];
+		v.normal[1] /= v.normal[3];
+		v.normal[2] /= v.normal[3];
+
+		*v
+	}).collect(), inds)
+}
+
+// the triangles whose centroid lies within [min, max) on X and Z, with only the vertices they use;
+// neighbouring ranges split the triangles between them without gaps or overlaps
+pub fn crop(verts: &[Vertex], inds: &[u32], min: [f32; 2], max: [f32; 2]) -> (Vec<Vertex>, Vec<u32>) {
+	let mut kept = Vec::new();
+	for t in inds.chunks_exact(3) {
+		let centroid = |axis: usize| (verts[t[0] as usize].pos[axis] + verts[t[1] as usize].pos[axis] + verts[t[2] as usize].pos[axis]) / 3.0;
+		let (x, z) = (centroid(0), centroid(2));
+		if x >= min[0] && x < max[0] && z >= min[1] && z < max[1] {
+			kept.extend_from_slice(t);
+		}
+	}
+	compact(verts, kept)
+}
+
+// the vertices used by `inds`, in the order of first use, and the indices pointing into them
+fn compact(verts: &[Vertex], mut inds: Vec<u32>) -> (Vec<Vertex>, Vec<u32>) {
+	let mut remap = HashMap::<u32, u32>::new();
+	let mut used = Vec::new();
+	for i in inds.iter_mut() {
+		*i = *remap.entry(*i).or_insert_with(|| {
+			used.push(verts[*i as usize]);
+			used.len() as u32 - 1
+		});
+	}
+	(used, inds)
+}
+
+// indices of the neighbors of every vertex
+pub fn adjacency(inds: &[u32], vertices: usize) -> Vec<Vec<u32>> {
+	let mut ret = vec![Vec::<u32>::new(); vertices];
+	for t in inds.chunks_exact(3) {
+		for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+			if !ret[a as usize].contains(&b) {
+				ret[a as usize].push(b);
+			}
+			if !ret[b as usize].contains(&a) {
+				ret[b as usize].push(a);
+			}
+		}
+	}
+
+	ret
+}
+
+// a node of the A* open set, ordered so that the lowest estimate is popped first
+struct Estimate(f32, usize);
+
+impl PartialEq for Estimate {
+	fn eq(&self, other: &Self) -> bool {
+		self.0 == other.0
+	}
+}
+impl Eq for Estimate {}
+impl PartialOrd for Estimate {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+impl Ord for Estimate {
+	fn cmp(&self, other: &Self) -> Ordering {
+		other.0.total_cmp(&self.0)
+	}
+}
+
+// shortest path along the mesh edges, the cost of an edge must not be lower than its length in XZ
+pub fn a_star(verts: &[Vertex], neighbors: &[Vec<u32>], start: usize, goal: usize, cost: impl Fn(usize, usize) -> f32) -> Option<Vec<usize>> {
+	let heuristic = |i: usize| ((verts[i].pos[0] - verts[goal].pos[0]).powi(2) + (verts[i].pos[2] - verts[goal].pos[2]).powi(2)).sqrt();
+
+	let mut costs = vec![f32::MAX; verts.len()];
+	let mut previous = vec![usize::MAX; verts.len()];
+	let mut open = BinaryHeap::new();
+	costs[start] = 0.0;
+	open.push(Estimate(heuristic(start), start));
+
+	while let Some(Estimate(_, i)) = open.pop() {
+		if i == goal {
+			let mut path = vec![goal];
+			while *path.last().unwrap() != start {
+				path.push(previous[*path.last().unwrap()]);
+			}
+			path.reverse();
+			return Some(path);
+		}
+
+		for n in &neighbors[i] {
+			let n = *n as usize;
+			let c = costs[i] + cost(i, n);
+			if c < costs[n] {
+				costs[n] = c;
+				previous[n] = i;
+				open.push(Estimate(c + heuristic(n), n));
+			}
+		}
+	}
+
+	None
+}
+
+// signed area of a triangle looking from above, times two
+fn orient_xz(verts: &[Vertex], a: u32, b: u32, c: u32) -> f32 {
+	let (a, b, c) = (verts[a as usize].pos, verts[b as usize].pos, verts[c as usize].pos);
+	(b[0] - a[0]) * (c[2] - a[2]) - (b[2] - a[2]) * (c[0] - a[0])
+}
+
+// one pass of edge flipping, returns the number of flipped edges.
+// An edge a -> b shared by two triangles (a, b, c) and (b, a, d) is replaced by the diagonal c - d if `rule(a, b, c, d)` says so.
+// The rule is asked only if the flip is possible, so a rule returning true always means a flip
+pub fn flip_edges(verts: &[Vertex], inds: &mut [u32], mut rule: impl FnMut(u32, u32, u32, u32) -> bool) -> usize {
+	let mut edges = HashMap::<(u32, u32), usize>::new();
+	for (t, tri) in inds.chunks_exact(3).enumerate() {
+		for k in 0..3 {
+			edges.insert((tri[k], tri[(k + 1) % 3]), t);
+		}
+	}
+
+	let mut touched = vec![false; inds.len() / 3];
+	let mut flips = 0;
+	for t in 0..inds.len() / 3 {
+		for k in 0..3 {
+			if touched[t] {
+				break;
+			}
+			// the shared edge goes a -> b in this triangle and b -> a in the other
+			let (a, b, c) = (inds[3*t + k], inds[3*t + (k + 1) % 3], inds[3*t + (k + 2) % 3]);
+			let Some(&u) = edges.get(&(b, a)) else { continue };
+			if touched[u] || u == t {
+				continue;
+			}
+			let d = inds[3*u..3*u + 3].iter().copied().find(|i| *i != a && *i != b).unwrap();
+			if c == d || edges.contains_key(&(c, d)) || edges.contains_key(&(d, c)) {
+				continue;
+			}
+
+			// the quad a, d, b, c has to be convex, so that the new triangles keep the orientation
+			let sign = orient_xz(verts, a, b, c).signum();
+			let (adc, dbc) = (orient_xz(verts, a, d, c), orient_xz(verts, d, b, c));
+			if adc.signum() != sign || dbc.signum() != sign || adc == 0.0 || dbc == 0.0 {
+				continue;
+			}
+
+			if !rule(a, b, c, d) {
+				continue;
+			}
+
+			for (x, y) in [(a, b), (b, c), (c, a), (b, a), (a, d), (d, b)] {
+				edges.remove(&(x, y));
+			}
+			inds[3*t..3*t + 3].copy_from_slice(&[a, d, c]);
+			inds[3*u..3*u + 3].copy_from_slice(&[d, b, c]);
+			for (x, y) in [(a, d), (d, c), (c, a)] {
+				edges.insert((x, y), t);
+			}
+			for (x, y) in [(d, b), (b, c), (c, d)] {
+				edges.insert((x, y), u);
+			}
+
+			touched[t] = true;
+			touched[u] = true;
+			flips += 1;
+		}
+	}
+
+	flips
+}
+
+// a cellular automaton rule in the B/S notation, e.g. "B3/S23": which neighbor counts give birth to a cell and which let it survive
+pub fn parse_rule(rule: &str) -> ([bool; 9], [bool; 9]) {
+	let mut birth = [false; 9];
+	let mut survival = [false; 9];
+
+	for part in rule.to_uppercase().split('/') {
+		let part = part.trim();
+		let target = match part.chars().next() {
+			Some('B') => &mut birth,
+			Some('S') => &mut survival,
+			_ => continue,
+		};
+		for d in part.chars().skip(1).filter_map(|c| c.to_digit(10)) {
+			if d < 9 {
+				target[d as usize] = true;
+			}
+		}
+	}
+
+	(birth, survival)
+}
+
+// Seamless tiling: the square footprint of the mesh is one period of the repeating terrain, so vertices on its opposite
+// edges are the same vertex. Every vertex maps to the twin with the lowest index (the canonical one), most vertices are their own twin.
+// Without tiling every vertex is only its own twin and nothing wraps.
+pub struct Tiles {
+	pub twins: Vec<usize>,
+	pub period: Option<f32>,
+	pub corner: [f32; 2],
+	offsets: Vec<[f32; 2]>,	// where a vertex lies relative to its canonical twin, in X and Z
+}
+
+impl Tiles {
+	pub fn new(verts: &[Vertex], tiling: bool) -> Self {
+		let (corner, period) = footprint(verts);
+		if !tiling || verts.is_empty() {
+			return Self { twins: (0..verts.len()).collect(), period: None, corner, offsets: vec![[0.0; 2]; verts.len()] };
+		}
+
+		let key = |p: f32, c: f32| ((p - c) / period * 10000.0).round() as i64 % 10000;
+		let mut canonical = HashMap::<(i64, i64), usize>::new();
+		let twins: Vec<usize> = verts.iter().enumerate().map(|(i, v)| *canonical.entry((key(v.pos[0], corner[0]), key(v.pos[2], corner[1]))).or_insert(i)).collect();
+		let offsets = verts.iter().zip(&twins).map(|(v, c)| {
+			let c = verts[*c].pos;
+			[((v.pos[0] - c[0]) / period).round() * period, ((v.pos[2] - c[2]) / period).round() * period]
+		}).collect();
+
+		Self { twins, period: Some(period), corner, offsets }
+	}
+
+	// the shortest way along an axis, across the opposite edges as well
+	pub fn wrap(&self, d: f32) -> f32 {
+		match self.period {
+			Some(p) => d - (d / p).round() * p,
+			None => d,
+		}
+	}
+
+	// neighbors of the canonical vertices, gathered from all of their twins, the other twins have none
+	pub fn adjacency(&self, inds: &[u32]) -> Vec<Vec<u32>> {
+		let mut neighbors = adjacency(inds, self.twins.len());
+		if self.period.is_none() {
+			return neighbors;
+		}
+
+		for i in 0..neighbors.len() {
+			let c = self.twins[i];
+			let mine: Vec<u32> = std::mem::take(&mut neighbors[i]).into_iter().map(|n| self.twins[n as usize] as u32).collect();
+			for n in mine {
+				if n as usize != c && !neighbors[c].contains(&n) {
+					neighbors[c].push(n);
+				}
+			}
+		}
+		neighbors
+	}
+
+	// copies the canonical vertices over their twins, keeping the twins on their own edge
+	pub fn weld(&self, verts: &mut [Vertex]) {
+		if self.period.is_none() {
+			return;
+		}
+		for i in 0..verts.len().min(self.twins.len()) {
+			let c = self.twins[i];
+			if c != i {
+				let mut v = verts[c];
+				v.pos[0] += self.offsets[i][0];
+				v.pos[2] += self.offsets[i][1];
+				verts[i] = v;
+			}
+		}
+	}
+}
+
+// the square around the mesh looking from above: its corner (x, z) and side length
+pub fn footprint(verts: &[Vertex]) -> ([f32; 2], f32) {
+	let mut min = [f32::MAX; 2];
+	let mut max = [f32::MIN; 2];
+	for v in verts {
+		min = [min[0].min(v.pos[0]), min[1].min(v.pos[2])];
+		max = [max[0].max(v.pos[0]), max[1].max(v.pos[2])];
+	}
+
+	(min, (max[0] - min[0]).max(max[1] - min[1]).max(0.0001))
+}
+
+// bilinear sample of an n x n grid at grid coordinates, the edges either wrap around or are clamped
+pub fn sample_grid(grid: &[f32], n: usize, x: f32, z: f32, wrap: bool) -> f32 {
+	let cell = |x: i64, z: i64| {
+		let (x, z) =
+			if wrap { (x.rem_euclid(n as i64), z.rem_euclid(n as i64)) }
+			else { (x.clamp(0, n as i64 - 1), z.clamp(0, n as i64 - 1)) };
+		grid[z as usize * n + x as usize]
+	};
+	let (x0, z0) = (x.floor() as i64, z.floor() as i64);
+	let (tx, tz) = (x - x0 as f32, z - z0 as f32);
+
+	(cell(x0, z0) * (1.0 - tx) + cell(x0 + 1, z0) * tx) * (1.0 - tz) +
+	(cell(x0, z0 + 1) * (1.0 - tx) + cell(x0 + 1, z0 + 1) * tx) * tz
+}
+
+// 3 x 3 box blur of an n x n grid, the edges are clamped
+pub fn blur_grid(grid: &[f32], n: usize) -> Vec<f32> {
+	(0..n * n).map(|i| {
+		let (x, z) = ((i % n) as i64, (i / n) as i64);
+		let mut total = 0.0;
+		for dz in -1..=1 {
+			for dx in -1..=1 {
+				let (nx, nz) = ((x + dx).clamp(0, n as i64 - 1), (z + dz).clamp(0, n as i64 - 1));
+				total += grid[nz as usize * n + nx as usize];
+			}
+		}
+		total / 9.0
+	}).collect()
+}
+
+// in-place radix-2 fast fourier transform of complex numbers stored as (re, im), the length has to be a power of two.
+// The inverse transform isn't normalized
+pub fn fft(data: &mut [(f32, f32)], inverse: bool) {
+	let n = data.len();
+
+	let mut j = 0;
+	for i in 1..n {
+		let mut bit = n >> 1;
+		while j & bit != 0 {
+			j ^= bit;
+			bit >>= 1;
+		}
+		j |= bit;
+		if i < j {
+			data.swap(i, j);
+		}
+	}
+
+	let mut len = 2;
+	while len <= n {
+		let angle = std::f32::consts::TAU / len as f32 * if inverse { 1.0 } else { -1.0 };
+		let w = (angle.cos(), angle.sin());
+		for start in (0..n).step_by(len) {
+			let mut wk = (1.0, 0.0);
+			for k in 0..len / 2 {
+				let (a, b) = (data[start + k], data[start + k + len / 2]);
+				let t = (b.0 * wk.0 - b.1 * wk.1, b.0 * wk.1 + b.1 * wk.0);
+				data[start + k] = (a.0 + t.0, a.1 + t.1);
+				data[start + k + len / 2] = (a.0 - t.0, a.1 - t.1);
+				wk = (wk.0 * w.0 - wk.1 * w.1, wk.0 * w.1 + wk.1 * w.0);
+			}
+		}
+		len <<= 1;
+	}
+}
+
+// edges used by only one triangle, in the direction they go in it and in the order of the triangles
+pub fn boundary_edges(inds: &[u32]) -> Vec<(u32, u32)> {
+	// like in `wireframe_indices`, an edge is identified by its smaller and larger index, here counting its triangles
+	let mut edges = HashMap::<(u32, u32), u32>::new();
+	for t in inds.chunks_exact(3) {
+		for (a, b) in [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])] {
+			*edges.entry((a.min(b), a.max(b))).or_default() += 1;
+		}
+	}
+
+	inds.chunks_exact(3)
+		.flat_map(|t| [(t[0], t[1]), (t[1], t[2]), (t[2], t[0])])
+		.filter(|(a, b)| edges[&(*a.min(b), *a.max(b))] == 1)
+		.collect()
+}
+
+// vertices lying on an edge used by only one triangle
+pub fn boundary_vertices(inds: &[u32], vertices: usize) -> Vec<bool> {
+	let mut ret = vec![false; vertices];
+	for (a, b) in boundary_edges(inds) {
+		ret[a as usize] = true;
+		ret[b as usize] = true;
+	}
+
+	ret
+}
+
+// extrudes the boundary edges straight down by `depth`, to hide the cracks between chunks or levels of detail;
+// the vertices at the bottom are copies of the ones above them, normals included
+pub fn skirt(mut verts: Vec<Vertex>, mut inds: Vec<u32>, depth: f32) -> (Vec<Vertex>, Vec<u32>) {
+	let mut below = HashMap::<u32, u32>::new();
+	for (a, b) in boundary_edges(&inds) {
+		let mut lower = |i: u32| *below.entry(i).or_insert_with(|| {
+			let mut v = verts[i as usize];
+			v.pos[1] -= depth;
+			verts.push(v);
+			verts.len() as u32 - 1
+		});
+		let (a_below, b_below) = (lower(a), lower(b));
+
+		// wound like the triangle that would continue the surface past the edge
+		inds.extend([b, a, a_below, b, a_below, b_below]);
+	}
+
+	(verts, inds)
+}
+
+// looks up the surface of a mesh from above, in a grid of buckets holding the overlapping triangles
+pub struct TriangleGrid {
+	verts: Vec<Vertex>,
+	inds: Vec<u32>,
+	cell: f32,
+	cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl TriangleGrid {
+	pub fn new(verts: &[Vertex], inds: &[u32]) -> Self {
+		let triangles = (inds.len() / 3).max(1);
+		let mut min = [f32::MAX; 2];
+		let mut max = [f32::MIN; 2];
+		for v in verts {
+			min = [min[0].min(v.pos[0]), min[1].min(v.pos[2])];
+			max = [max[0].max(v.pos[0]), max[1].max(v.pos[2])];
+		}
+		let area = ((max[0] - min[0]) * (max[1] - min[1])).max(0.0001);
+		let cell = (area / triangles as f32).sqrt() * 2.0;
+
+		let mut cells = HashMap::<(i32, i32), Vec<usize>>::new();
+		for (t, tri) in inds.chunks_exact(3).enumerate() {
+			let tri = [tri[0], tri[1], tri[2]];
+			let xs = tri.map(|i| verts[i as usize].pos[0]);
+			let zs = tri.map(|i| verts[i as usize].pos[2]);
+			let (x0, x1) = ((xs[0].min(xs[1]).min(xs[2]) / cell).floor() as i32, (xs[0].max(xs[1]).max(xs[2]) / cell).floor() as i32);
+			let (z0, z1) = ((zs[0].min(zs[1]).min(zs[2]) / cell).floor() as i32, (zs[0].max(zs[1]).max(zs[2]) / cell).floor() as i32);
+			for x in x0..=x1 {
+				for z in z0..=z1 {
+					cells.entry((x, z)).or_default().push(t);
+				}
+			}
+		}
+
+		Self { verts: verts.to_vec(), inds: inds.to_vec(), cell, cells }
+	}
+
+	// the surface point above or below (x, z), with its attributes and color interpolated
+	pub fn sample(&self, x: f32, z: f32) -> Option<Vertex> {
+		let bucket = self.cells.get(&((x / self.cell).floor() as i32, (z / self.cell).floor() as i32))?;
+		for t in bucket {
+			let [a, b, c] = [0, 1, 2].map(|k| self.verts[self.inds[3*t + k] as usize]);
+			let det = (b.pos[2] - c.pos[2]) * (a.pos[0] - c.pos[0]) + (c.pos[0] - b.pos[0]) * (a.pos[2] - c.pos[2]);
+			if det == 0.0 {
+				continue;
+			}
+			let wa = ((b.pos[2] - c.pos[2]) * (x - c.pos[0]) + (c.pos[0] - b.pos[0]) * (z - c.pos[2])) / det;
+			let wb = ((c.pos[2] - a.pos[2]) * (x - c.pos[0]) + (a.pos[0] - c.pos[0]) * (z - c.pos[2])) / det;
+			let wc = 1.0 - wa - wb;
+			const EPSILON: f32 = -0.0001;
+			if wa < EPSILON || wb < EPSILON || wc < EPSILON {
+				continue;
+			}
+
+			let mut v = a;
+			for k in 0..4 {
+				v.pos[k] = a.pos[k] * wa + b.pos[k] * wb + c.pos[k] * wc;
+				v.color[k] = a.color[k] * wa + b.color[k] * wb + c.color[k] * wc;
+			}
+			for k in 0..v.attributes.len() {
+				v.attributes[k] = a.attributes[k] * wa + b.attributes[k] * wb + c.attributes[k] * wc;
+			}
+			return Some(v);
+		}
+
+		None
+	}
+}
+
+// splits every edge longer than `high` in half, triangles are split into 2, 3 or 4 depending on how many of their edges were split
+pub fn split_long_edges(verts: &mut Vec<Vertex>, inds: &[u32], high: f32) -> Vec<u32> {
+	let mut mids = HashMap::<(u32, u32), u32>::new();
+	let mut ret = Vec::with_capacity(inds.len());
+
+	for t in inds.chunks_exact(3) {
+		let mut tri = [t[0], t[1], t[2]];
+		let mut split = [None; 3];
+		for k in 0..3 {
+			let (a, b) = (tri[k], tri[(k + 1) % 3]);
+			let (va, vb) = (verts[a as usize], verts[b as usize]);
+			if (Vector4::from(va.pos) - Vector4::from(vb.pos)).magnitude() > high {
+				split[k] = Some(*mids.entry((a.min(b), a.max(b))).or_insert_with(|| {
+					verts.push(Vertex::mid_ignore_normals(va, vb));
+					verts.len() as u32 - 1
+				}));
+			}
+		}
+
+		match split.iter().filter(|s| s.is_some()).count() {
+			0 => ret.extend_from_slice(&tri),
+			1 => {
+				// rotated so that the split edge is 0 -> 1
+				while split[0].is_none() {
+					tri.rotate_left(1);
+					split.rotate_left(1);
+				}
+				let m = split[0].unwrap();
+				ret.extend_from_slice(&[tri[0], m, tri[2], m, tri[1], tri[2]]);
+			},
+			2 => {
+				// rotated so that the edge which isn't split is 2 -> 0
+				while split[2].is_some() {
+					tri.rotate_left(1);
+					split.rotate_left(1);
+				}
+				let (m0, m1) = (split[0].unwrap(), split[1].unwrap());
+				ret.extend_from_slice(&[m0, tri[1], m1, tri[0], m0, m1, tri[0], m1, tri[2]]);
+			},
+			_ => {
+				let (m0, m1, m2) = (split[0].unwrap(), split[1].unwrap(), split[2].unwrap());
+				ret.extend_from_slice(&[tri[0], m0, m2, m0, tri[1], m1, m2, m0, m1, m2, m1, tri[2]]);
+			},
+		}
+	}
+
+	ret
+}
+
+// collapses edges shorter than `low`, unless it would create an edge longer than `high`, flip a triangle or change the boundary.
+// Vertices around a collapse are locked until the next call, so that the collapses don't interfere
+pub fn collapse_short_edges(mut verts: Vec<Vertex>, inds: Vec<u32>, low: f32, high: f32) -> (Vec<Vertex>, Vec<u32>) {
+	let neighbors = adjacency(&inds, verts.len());
+	let boundary = boundary_vertices(&inds, verts.len());
+	let mut triangles = vec![Vec::<usize>::new(); verts.len()];
+	for (t, tri) in inds.chunks_exact(3).enumerate() {
+		for i in tri {
+			triangles[*i as usize].push(t);
+		}
+	}
+
+	let pos = |v: &Vertex| Vector4::from(v.pos).truncate();
+	let mut locked = vec![false; verts.len()];
+	let mut remap: Vec<u32> = (0..verts.len() as u32).collect();
+
+	for t in inds.chunks_exact(3) {
+		for k in 0..3 {
+			let (mut keep, mut remove) = (t[k] as usize, t[(k + 1) % 3] as usize);
+			if locked[keep] || locked[remove] || (pos(&verts[keep]) - pos(&verts[remove])).magnitude() >= low {
+				continue;
+			}
+			if boundary[keep] && boundary[remove] {
+				continue;
+			}
+			if boundary[remove] {
+				(keep, remove) = (remove, keep);
+			}
+
+			// an interior edge has exactly two common neighbors, otherwise the collapse would break the surface
+			if neighbors[keep].iter().filter(|n| neighbors[remove].contains(n)).count() != 2 {
+				continue;
+			}
+
+			let merged = if boundary[keep] { verts[keep] } else { Vertex::mid_ignore_normals(verts[keep], verts[remove]) };
+			let merged_pos = pos(&merged);
+			if neighbors[keep].iter().chain(neighbors[remove].iter()).any(|n| (pos(&verts[*n as usize]) - merged_pos).magnitude() > high) {
+				continue;
+			}
+
+			let flips = triangles[keep].iter().chain(triangles[remove].iter()).any(|t| {
+				let tri = [inds[3*t], inds[3*t + 1], inds[3*t + 2]];
+				if tri.contains(&(keep as u32)) && tri.contains(&(remove as u32)) {
+					return false;
+				}
+				let before = tri.map(|i| pos(&verts[i as usize]));
+				let after = tri.map(|i| if i as usize == keep || i as usize == remove { merged_pos } else { pos(&verts[i as usize]) });
+				let normal = |p: [cgmath::Vector3<f32>; 3]| (p[1] - p[0]).cross(p[2] - p[0]);
+				normal(before).dot(normal(after)) <= 0.0
+			});
+			if flips {
+				continue;
+			}
+
+			verts[keep] = merged;
+			remap[remove] = keep as u32;
+			for i in [keep, remove].into_iter().chain(neighbors[keep].iter().chain(neighbors[remove].iter()).map(|n| *n as usize)) {
+				locked[i] = true;
+			}
+		}
+	}
+
+	let mut used = vec![u32::MAX; verts.len()];
+	let mut verts_out = Vec::with_capacity(verts.len());
+	let mut inds_out = Vec::with_capacity(inds.len());
+	for t in inds.chunks_exact(3) {
+		let tri = [remap[t[0] as usize], remap[t[1] as usize], remap[t[2] as usize]];
+		if tri[0] == tri[1] || tri[1] == tri[2] || tri[2] == tri[0] {
+			continue;
+		}
+		for i in tri {
+			if used[i as usize] == u32::MAX {
+				used[i as usize] = verts_out.len() as u32;
+				verts_out.push(verts[i as usize]);
+			}
+			inds_out.push(used[i as usize]);
+		}
+	}
+
+	(verts_out, inds_out)
+}
+
+// samples a catmull-rom spline through the points, roughly every `step` units
+pub fn catmull_rom(points: &[[f32; 2]], step: f32) -> Vec<[f32; 2]> {
+	if points.len() < 2 {
+		return points.to_vec();
+	}
+
+	let mut ret = Vec::new();
+	for i in 0..points.len() - 1 {
+		let p0 = points[i.saturating_sub(1)];
+		let p1 = points[i];
+		let p2 = points[i + 1];
+		let p3 = points[(i + 2).min(points.len() - 1)];
+
+		let length = ((p2[0] - p1[0]).powi(2) + (p2[1] - p1[1]).powi(2)).sqrt();
+		let samples = ((length / step.max(0.001)).ceil() as usize).max(1);
+		for s in 0..samples {
+			let t = s as f32 / samples as f32;
+			let (t2, t3) = (t * t, t * t * t);
+			let mut p = [0.0; 2];
+			for c in 0..2 {
+				p[c] = 0.5 * (
+					2.0 * p1[c] +
+					(p2[c] - p0[c]) * t +
+					(2.0 * p0[c] - 5.0 * p1[c] + 4.0 * p2[c] - p3[c]) * t2 +
+					(3.0 * p1[c] - p0[c] - 3.0 * p2[c] + p3[c]) * t3
+				);
+			}
+			ret.push(p);
+		}
+	}
+	ret.push(points[points.len() - 1]);
+
+	ret
+}
+
+// flattens a corridor along the path to a graded height profile, the shoulders blend it back into the terrain.
+// The profile is the terrain height under the path, smoothed `smoothing` times. Returns the road's centerline.
+pub fn carve_road(verts: &mut [Vertex], path: &[[f32; 2]], width: f32, shoulder: f32, smoothing: u32) -> Vec<[f32; 3]> {
+	if path.len() < 2 || verts.is_empty() {
+		return Vec::new();
+	}
+
+	let dist2 = |v: &Vertex, p: [f32; 2]| (v.pos[0] - p[0]).powi(2) + (v.pos[2] - p[1]).powi(2);
+
+	let mut heights: Vec<f32> = path.iter().map(|p| {
+		let (mut sum, mut count) = (0.0, 0.0);
+		for v in verts.iter() {
+			if dist2(v, *p) < width * width {
+				sum += v.pos[1];
+				count += 1.0;
+			}
+		}
+		if count > 0.0 {
+			sum / count
+		}
+		else {
+			verts.iter().min_by(|a, b| dist2(a, *p).total_cmp(&dist2(b, *p))).unwrap().pos[1]
+		}
+	}).collect();
+
+	for _ in 0..smoothing {
+		heights = (0..heights.len()).map(|i| {
+			if i == 0 || i + 1 == heights.len() {
+				heights[i]
+			}
+			else {
+				(heights[i - 1] + heights[i] + heights[i + 1]) / 3.0
+			}
+		}).collect();
+	}
+
+	let half = width * 0.5;
+	for v in verts.iter_mut() {
+		let (x, z) = (v.pos[0], v.pos[2]);
+		let mut closest = (f32::MAX, 0.0);
+		for i in 0..path.len() - 1 {
+			let (a, b) = (path[i], path[i + 1]);
+			let (dx, dz) = (b[0] - a[0], b[1] - a[1]);
+			let len2 = dx * dx + dz * dz;
+			let t = if len2 > 0.0 { (((x - a[0]) * dx + (z - a[1]) * dz) / len2).clamp(0.0, 1.0) } else { 0.0 };
+			let d = (x - a[0] - dx * t).powi(2) + (z - a[1] - dz * t).powi(2);
+			if d < closest.0 {
+				closest = (d, heights[i] * (1.0 - t) + heights[i + 1] * t);
+			}
+		}
+
+		let (d, h) = (closest.0.sqrt(), closest.1);
+		if d <= half {
+			v.pos[1] = h;
+		}
+		else if d < half + shoulder {
+			let t = (d - half) / shoulder;
+			let t = t * t * (3.0 - 2.0 * t);
+			v.pos[1] = h * (1.0 - t) + v.pos[1] * t;
+		}
+	}
+
+	path.iter().zip(heights).map(|(p, h)| [p[0], h, p[1]]).collect()
 }
 
 // todo: there are random (?) missing lines sometimes
@@ -4,6 +4,7 @@ struct Uniform {
 	display: u32,
 	material_threshold: f32,
 	material_smoothness: f32,
+	overlay: i32,
 }
 
 @group(0) @binding(0)
@@ -13,12 +14,22 @@ var<uniform> uni: Uniform;
 struct VertexIn {
 	@location(0) pos: vec4<f32>,
 	@location(1) normal: vec4<f32>,
+	@location(2) color: vec4<f32>,
+	// attribute channels, see vertex::Attribute for the indices
+	@location(3) attributes0: vec4<f32>,
+	@location(4) attributes1: vec4<f32>,
+	@location(5) attributes2: vec4<f32>,
+	@location(6) attributes3: vec4<f32>,
 }
 
 struct VertexOut {
 	@builtin(position) pos_builtin: vec4<f32>,
 	@location(0) pos_world: vec4<f32>,
 	@location(2) normal: vec4<f32>,
+	@location(3) color: vec4<f32>,
+	@location(4) materials: vec4<f32>, // grass, rock, sand, snow
+	@location(5) overlay: f32,
+	@location(6) occlusion: f32,
 }
 
 
@@ -29,6 +40,17 @@ fn vs_main(in: VertexIn) -> VertexOut {
 	out.pos_builtin = uni.proj * in.pos;
 	out.pos_world = in.pos;
 	out.normal = in.normal;
+	out.color = in.color;
+	out.materials = in.attributes1;
+	out.occlusion = in.attributes2.y;
+
+	var attributes = array<vec4<f32>, 4>(in.attributes0, in.attributes1, in.attributes2, in.attributes3);
+	if uni.overlay >= 0 {
+		out.overlay = attributes[uni.overlay / 4][uni.overlay % 4];
+	}
+	else {
+		out.overlay = 0.0;
+	}
 
 	return out;
 }
@@ -97,6 +119,8 @@ fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
 	// let rock =  material(vec3(0.1, 0.3, 0.9), vec3(0.1, 0.5, 1.0), 0.1, 0.6, 0.4, 0.7);
 	let grass = material(vec3(0.1, 0.7, 0.3), vec3(0.3, 0.8, 1.0), 0.6, 0.7, 0.2, 0.6);
 	let rock =  material(vec3(0.2, 0.2, 0.2), vec3(0.3, 0.8, 1.0), 0.6, 0.9, 0.4, 0.7);
+	let sand =  material(vec3(0.8, 0.7, 0.4), vec3(0.3, 0.8, 1.0), 0.6, 0.8, 0.1, 0.8);
+	let snow =  material(vec3(0.9, 0.9, 1.0), vec3(0.3, 0.8, 1.0), 0.6, 0.7, 0.5, 0.4);
 
 	let light_color = vec3(1.0, 0.9, 0.7);
 
@@ -117,12 +141,36 @@ fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
 
 	let t = smthstp(-normal.y, uni.material_threshold - uni.material_smoothness, uni.material_threshold + uni.material_smoothness);
 	// let t = smthstp(-normal.y, 0.6, 0.8);
-	let material = lerp_material(rock, grass, t);
+	var material = lerp_material(rock, grass, t);
+
+	// material weights cover the slope-based material
+	let weights = max(in.materials, vec4(0.0));
+	let weight_sum = weights.x + weights.y + weights.z + weights.w;
+	if weight_sum > 0.0 {
+		var weighted = lerp_material(grass, rock, weights.y / (weights.x + weights.y + 0.0001));
+		weighted = lerp_material(weighted, sand, weights.z / (weights.x + weights.y + weights.z + 0.0001));
+		weighted = lerp_material(weighted, snow, weights.w / weight_sum);
+		material = lerp_material(material, weighted, min(weight_sum, 1.0));
+	}
 
+	material.surface_color = mix(material.surface_color, in.color.rgb, clamp(in.color.a, 0.0, 1.0));
 
-	let ambient =   get_ambient(material);
+	if uni.overlay >= 0 {
+		let v = clamp(in.overlay, 0.0, 1.0);
+		material.surface_color = mix(mix(vec3(0.1, 0.2, 0.9), vec3(0.1, 0.8, 0.2), min(v * 2.0, 1.0)), vec3(0.9, 0.2, 0.1), max(v * 2.0 - 1.0, 0.0));
+	}
+
+
+	// baked ambient occlusion darkens the valleys
+	let ambient =   get_ambient(material) * (1.0 - clamp(in.occlusion, 0.0, 1.0));
 	let diffuse =   get_diffuse(material, light_color, light_dir, normal);
 	let specular = get_specular(material, view_dir, light_dir, normal, light_color);
 
 	return vec4(vec3(ambient + diffuse + specular), 1.0);
+}
+
+// scatter markers and other line layers, drawn with their own color
+@fragment
+fn fs_marker(in: VertexOut) -> @location(0) vec4<f32> {
+	return vec4(in.color.rgb, 1.0);
 }
\ No newline at end of file
@@ -1,10 +1,73 @@
 use egui_wgpu::wgpu;
 
+pub const ATTRIBUTES: usize = 16;
+
+// Named channels of `Vertex::attributes`. The discriminant is the index into the array,
+// shader.wgsl reads the channels by these indices, so keep them in sync.
+// Slots without a variant are reserved.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Attribute {
+	Moisture = 0,
+	Flow = 1,
+	Sediment = 2,
+	Biome = 3, // index of the rule in the Biomes operation, -1 - no rule matched
+
+	// material weights, blended over the slope-based material in the viewport
+	Grass = 4,
+	Rock = 5,
+	Sand = 6,
+	Snow = 7,
+
+	Stratum = 8, // index of the rock layer exposed at the vertex, -1 - no strata
+	Occlusion = 9, // baked ambient occlusion, 0 - open sky, 1 - fully occluded
+	Insolation = 10, // sun exposure, 1 - facing the sun the whole time it is above the horizon
+	Visibility = 11, // 1 - seen from the Viewshed observer, 0 - hidden
+	Walkable = 12, // 1 - reachable from the spawn, 0.5 - walkable but unreachable, 0 - not walkable
+}
+
+impl Attribute {
+	pub const ALL: [Attribute; 13] = [
+		Attribute::Moisture,
+		Attribute::Flow,
+		Attribute::Sediment,
+		Attribute::Biome,
+		Attribute::Grass,
+		Attribute::Rock,
+		Attribute::Sand,
+		Attribute::Snow,
+		Attribute::Stratum,
+		Attribute::Occlusion,
+		Attribute::Insolation,
+		Attribute::Visibility,
+		Attribute::Walkable,
+	];
+
+	pub fn name(&self) -> &'static str {
+		match self {
+			Self::Moisture => "moisture",
+			Self::Flow => "flow",
+			Self::Sediment => "sediment",
+			Self::Biome => "biome",
+			Self::Grass => "grass",
+			Self::Rock => "rock",
+			Self::Sand => "sand",
+			Self::Snow => "snow",
+			Self::Stratum => "stratum",
+			Self::Occlusion => "occlusion",
+			Self::Insolation => "insolation",
+			Self::Visibility => "visibility",
+			Self::Walkable => "walkable",
+		}
+	}
+}
+
 #[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+#[derive(PartialEq, Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
 	pub pos: [f32; 4],
 	pub normal: [f32; 4],
+	pub color: [f32; 4], // alpha is how much the color replaces the material's surface color
+	pub attributes: [f32; ATTRIBUTES],
 }
 
 impl Vertex {
@@ -12,26 +75,51 @@ impl Vertex {
 		Self {
 			pos: [x, y, z, 1.0],
 			normal: [nx, ny, nz, 1.0],
+			color: [1.0, 1.0, 1.0, 0.0],
+			attributes: [0.0; ATTRIBUTES],
 		}
 	}
 
+	pub fn get(&self, attribute: Attribute) -> f32 {
+		self.attributes[attribute as usize]
+	}
+
+	pub fn set(&mut self, attribute: Attribute, value: f32) {
+		self.attributes[attribute as usize] = value;
+	}
+
+	// attributes and color are averaged as well, so that subdivision keeps them
 	pub fn mid_ignore_normals(one: Vertex, two: Vertex) -> Vertex {
-		Vertex { 
+		let mut attributes = [0.0; ATTRIBUTES];
+		for (i, a) in attributes.iter_mut().enumerate() {
+			*a = one.attributes[i] * 0.5 + two.attributes[i] * 0.5;
+		}
+
+		Vertex {
 			pos: [
 				one.pos[0] * 0.5 + two.pos[0] * 0.5,
 				one.pos[1] * 0.5 + two.pos[1] * 0.5,
 				one.pos[2] * 0.5 + two.pos[2] * 0.5,
 				one.pos[3] * 0.5 + two.pos[3] * 0.5,
-			], 
-			normal: one.normal
+			],
+			normal: one.normal,
+			color: [
+				one.color[0] * 0.5 + two.color[0] * 0.5,
+				one.color[1] * 0.5 + two.color[1] * 0.5,
+				one.color[2] * 0.5 + two.color[2] * 0.5,
+				one.color[3] * 0.5 + two.color[3] * 0.5,
+			],
+			attributes,
 		}
 	}
 
 
 	pub fn buffer_layout<'a>() -> wgpu::VertexBufferLayout<'a> {
-		wgpu::VertexBufferLayout { 
-			array_stride: std::mem::size_of::<Vertex>() as u64, 
-			step_mode: wgpu::VertexStepMode::Vertex, 
+		const VEC4: u64 = std::mem::size_of::<[f32; 4]>() as u64;
+
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<Vertex>() as u64,
+			step_mode: wgpu::VertexStepMode::Vertex,
 			attributes: &[
 				wgpu::VertexAttribute {
 					offset: 0,
@@ -39,11 +127,37 @@ impl Vertex {
 					shader_location: 0,
 				},
 				wgpu::VertexAttribute {
-					offset: std::mem::size_of::<[f32; 4]>() as u64,
+					offset: VEC4,
 					format: wgpu::VertexFormat::Float32x4,
 					shader_location: 1,
 				},
-			], 
+				wgpu::VertexAttribute {
+					offset: 2 * VEC4,
+					format: wgpu::VertexFormat::Float32x4,
+					shader_location: 2,
+				},
+				// attributes, four channels per location
+				wgpu::VertexAttribute {
+					offset: 3 * VEC4,
+					format: wgpu::VertexFormat::Float32x4,
+					shader_location: 3,
+				},
+				wgpu::VertexAttribute {
+					offset: 4 * VEC4,
+					format: wgpu::VertexFormat::Float32x4,
+					shader_location: 4,
+				},
+				wgpu::VertexAttribute {
+					offset: 5 * VEC4,
+					format: wgpu::VertexFormat::Float32x4,
+					shader_location: 5,
+				},
+				wgpu::VertexAttribute {
+					offset: 6 * VEC4,
+					format: wgpu::VertexFormat::Float32x4,
+					shader_location: 6,
+				},
+			],
 		}
 	}
-}
\ No newline at end of file
+}
@@ -8,7 +8,7 @@ pub struct ViewUniform {
 	pub display: u32, // 0 - wireframe, 1 - flat, 2 - smooth
 	pub material_threshold: f32,
 	pub material_smoothness: f32,
-	pub _p1: u32,
+	pub overlay: i32, // index of the attribute channel shown over the terrain, -1 - none
 }
 
 impl ViewUniform {
@@ -18,7 +18,7 @@ impl ViewUniform {
 			display,
 			material_smoothness,
 			material_threshold,
-			_p1: 0,
+			overlay: -1,
 		}
 	}
 }
\ No newline at end of file
@@ -9,7 +9,10 @@ pub struct Viewport {
 	pub index_buffer: wgpu::Buffer,
     pub triangle_pipeline: wgpu::RenderPipeline,
     pub wireframe_pipeline: wgpu::RenderPipeline,
+	pub marker_pipeline: wgpu::RenderPipeline,
+	pub marker_buffer: wgpu::Buffer,
 	pub indices: u32,
+	pub marker_vertices: u32,
 	pub camera: camera::OrbitCamera,
 	pub view_uniform: view::ViewUniform,
 	pub uniform_buffer: wgpu::Buffer,
@@ -18,6 +21,7 @@ pub struct Viewport {
 	pub depth_texture_view: wgpu::TextureView,
 
 	pub mesh: (Vec<Vertex>, Vec<u32>, Option<Vec<u32>>),
+	pub markers: Vec<Vertex>,
 	pub should_set: bool,
 }
 
@@ -41,6 +45,12 @@ impl Viewport {
 			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::INDEX,
 		});
 
+		let marker_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: None,
+			contents: bytemuck::cast_slice(vertices.as_slice()),
+			usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
+		});
+
 		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
 			label: None,
 			source: wgpu::ShaderSource::Wgsl(include_str!("shader.wgsl").into()),
@@ -144,13 +154,23 @@ impl Viewport {
 		let triangle_pipeline = device.create_render_pipeline(&pipeline_desc);
 		pipeline_desc.primitive.topology = wgpu::PrimitiveTopology::LineList;
 		let wireframe_pipeline = device.create_render_pipeline(&pipeline_desc);
+		let marker_fragment = wgpu::FragmentState {
+			module: &shader,
+			entry_point: "fs_marker",
+			targets: &pipeline_bindings,
+		};
+		pipeline_desc.fragment = Some(marker_fragment);
+		let marker_pipeline = device.create_render_pipeline(&pipeline_desc);
 
 		Self { 
 			vertex_buffer,
 			index_buffer,
 			triangle_pipeline, 
 			wireframe_pipeline, 
+			marker_pipeline,
+			marker_buffer,
 			indices: indices.len() as u32,
+			marker_vertices: 0,
 			camera,
 			view_uniform,
 			uniform_buffer,
@@ -158,6 +178,7 @@ impl Viewport {
 			depth_texture,
 			depth_texture_view,
 			mesh: (Vec::new(), Vec::new(), None),
+			markers: Vec::new(),
 			should_set: false,
 		}
 	}
@@ -228,6 +249,14 @@ impl Viewport {
 			});
 
 			self.indices = self.mesh.1.len() as u32;
+
+			self.marker_buffer.destroy();
+			self.marker_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+				label: None,
+				contents: bytemuck::cast_slice(self.markers.as_slice()),
+				usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::VERTEX,
+			});
+			self.marker_vertices = self.markers.len() as u32;
 		}
 
 		// move camera
@@ -243,5 +272,9 @@ impl Viewport {
 		render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
 		render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
         render_pass.draw_indexed(0..self.indices, 0, 0..1);
+
+		render_pass.set_pipeline(&self.marker_pipeline);
+		render_pass.set_vertex_buffer(0, self.marker_buffer.slice(..));
+		render_pass.draw(0..self.marker_vertices, 0..1);
     }
 }
\ No newline at end of file